@@ -14,6 +14,8 @@ use serde::{Deserialize, Serialize};
 use doorbell::nvs::NVStore;
 use doorbell::web::{FlashMsg, NavBar};
 
+use crate::shadow::ShadowConfig;
+
 const ADC_SAMPLE_RATE: u32 = 1000; // 1kHz sample rate
 const ADC_BUFFER_LEN: usize = 50; // 50ms sample buffer
 const ADC_MIN_THRESHOLD: f32 = 0.1; // If Hall-Effect sensor is on we should see Vcc/2
@@ -263,7 +265,7 @@ pub fn check_ring(
     prev: &[f32; THRESHOLD_BUFFER],
 ) -> (bool, f32, [f32; THRESHOLD_BUFFER]) {
     let stdev_avg = prev.iter().sum::<f32>() / prev.len() as f32;
-    let multiplier = THRESHOLD_MULTIPLIER.load(Ordering::Relaxed) as f32 / 1000_f32;
+    let multiplier = threshold_multiplier();
     let threshold = multiplier * stdev_avg;
     let ring = stddev > threshold;
     let prev = if mean > ADC_MIN_THRESHOLD && !ring {
@@ -275,6 +277,52 @@ pub fn check_ring(
     (ring, threshold, prev)
 }
 
+pub fn threshold_multiplier() -> f32 {
+    THRESHOLD_MULTIPLIER.load(Ordering::Relaxed) as f32 / 1000_f32
+}
+
+// Update THRESHOLD_MULTIPLIER and save to NVS
+pub fn set_threshold_multiplier(threshold_multiplier: f32) -> anyhow::Result<()> {
+    if !threshold_multiplier.is_finite() || threshold_multiplier <= 0.0 {
+        anyhow::bail!("Invalid threshold_multiplier: {threshold_multiplier}");
+    }
+    NVStore::set::<AdcParams>(
+        "adc",
+        &AdcParams {
+            threshold_multiplier,
+        },
+    )?;
+    THRESHOLD_MULTIPLIER.store((threshold_multiplier * 1000_f32) as i32, Ordering::Relaxed);
+    Ok(())
+}
+
+impl ShadowConfig for AdcParams {
+    fn load() -> anyhow::Result<Self> {
+        Ok(Self {
+            threshold_multiplier: threshold_multiplier(),
+        })
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if !self.threshold_multiplier.is_finite() || self.threshold_multiplier <= 0.0 {
+            anyhow::bail!(
+                "Invalid threshold_multiplier: {}",
+                self.threshold_multiplier
+            );
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        set_threshold_multiplier(self.threshold_multiplier)
+    }
+
+    // Applied immediately
+    fn restart_required() -> bool {
+        false
+    }
+}
+
 // HTTP Handlers
 pub fn adc_set_params(mut request: Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
     let mut buf = [0_u8; 1024];
@@ -283,11 +331,7 @@ pub fn adc_set_params(mut request: Request<&mut EspHttpConnection>) -> anyhow::R
 
     match serde_json::from_slice::<AdcParams>(&buf[0..len]) {
         Ok(c) => {
-            NVStore::set::<AdcParams>("adc", &c)?;
-            THRESHOLD_MULTIPLIER.store(
-                (c.threshold_multiplier * 1000_f32) as i32,
-                Ordering::Relaxed,
-            );
+            set_threshold_multiplier(c.threshold_multiplier)?;
             let flash = serde_json::to_string(&FlashMsg {
                 level: "success",
                 message: "Successfully updated AdcParams",
@@ -0,0 +1,143 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use log::LevelFilter;
+use serde::Serialize;
+
+use doorbell::mqtt::{parse_level, MqttLogger};
+
+use crate::adc::{RingMessage, ADC_DEBUG, ADC_STATS};
+
+// Remote commands received on <status_topic>/cmd/<command>
+pub const COMMANDS: [&str; 7] = [
+    "reboot",
+    "test_ring",
+    "adc_debug",
+    "threshold",
+    "dnd",
+    "log",
+    "ota",
+];
+
+// Do Not Disturb - suppress Pushover notifications
+pub static DND: AtomicBool = AtomicBool::new(false);
+
+// Used to inject test ring into main loop
+static RING_TX: Mutex<Option<mpsc::Sender<RingMessage>>> = Mutex::new(None);
+
+const TEST_RING_DURATION: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub enum Command {
+    Reboot,
+    TestRing,
+    AdcDebug(bool),
+    Threshold(f32),
+    Dnd(bool),
+    Log(Option<LevelFilter>), // None disables log forwarding
+    Ota(String),
+}
+
+#[derive(Serialize, Debug)]
+pub struct CommandResponse<'a> {
+    pub command: &'a str,
+    pub ok: bool,
+    pub message: String,
+}
+
+pub fn init(ring_tx: mpsc::Sender<RingMessage>) -> anyhow::Result<()> {
+    RING_TX.replace(Some(ring_tx))?;
+    Ok(())
+}
+
+fn parse_bool(payload: &str) -> anyhow::Result<bool> {
+    match payload.trim().to_ascii_lowercase().as_str() {
+        "on" | "true" | "1" => Ok(true),
+        "off" | "false" | "0" => Ok(false),
+        _ => Err(anyhow::anyhow!("Expected ON/OFF: {payload}")),
+    }
+}
+
+impl Command {
+    pub fn parse(name: &str, payload: &str) -> anyhow::Result<Self> {
+        match name {
+            "reboot" => Ok(Command::Reboot),
+            "test_ring" => Ok(Command::TestRing),
+            "adc_debug" => Ok(Command::AdcDebug(parse_bool(payload)?)),
+            "threshold" => Ok(Command::Threshold(payload.trim().parse::<f32>()?)),
+            "dnd" => Ok(Command::Dnd(parse_bool(payload)?)),
+            // ON/OFF or minimum level
+            "log" => match parse_bool(payload) {
+                Ok(true) => Ok(Command::Log(Some(MqttLogger::level()))),
+                Ok(false) => Ok(Command::Log(None)),
+                Err(_) => Ok(Command::Log(Some(parse_level(payload)?))),
+            },
+            "ota" => {
+                let url = payload.trim();
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    anyhow::bail!("Invalid OTA URL: {url}");
+                }
+                Ok(Command::Ota(url.to_string()))
+            }
+            _ => Err(anyhow::anyhow!("Unknown command: {name}")),
+        }
+    }
+
+    pub fn execute(self) -> anyhow::Result<String> {
+        match self {
+            Command::Reboot => {
+                thread::spawn(|| {
+                    thread::sleep(Duration::from_secs(1));
+                    esp_idf_hal::reset::restart();
+                });
+                Ok("Rebooting".to_string())
+            }
+            Command::TestRing => {
+                let tx = RING_TX
+                    .get_cloned()?
+                    .ok_or_else(|| anyhow::anyhow!("Ring channel not initialised"))?;
+                let stats = ADC_STATS
+                    .get_cloned()?
+                    .ok_or_else(|| anyhow::anyhow!("No ADC stats"))?;
+                tx.send(RingMessage::RingStart(stats))?;
+                thread::spawn(move || {
+                    thread::sleep(TEST_RING_DURATION);
+                    let _ = tx.send(RingMessage::RingStop);
+                });
+                Ok("Test ring sent".to_string())
+            }
+            Command::AdcDebug(v) => {
+                ADC_DEBUG.store(v, Ordering::Relaxed);
+                Ok(format!("ADC_DEBUG: {v}"))
+            }
+            Command::Threshold(v) => {
+                crate::adc::set_threshold_multiplier(v)?;
+                Ok(format!("threshold_multiplier: {v}"))
+            }
+            Command::Dnd(v) => {
+                DND.store(v, Ordering::Relaxed);
+                Ok(format!("DND: {v}"))
+            }
+            Command::Log(Some(level)) => {
+                MqttLogger::set_level(level);
+                MqttLogger::set_enabled(true);
+                Ok(format!("Log forwarding: {level}"))
+            }
+            Command::Log(None) => {
+                MqttLogger::set_enabled(false);
+                Ok("Log forwarding: OFF".to_string())
+            }
+            Command::Ota(url) => {
+                // OTA takes some time so run in background (device restarts on success)
+                thread::Builder::new().stack_size(8192).spawn(move || {
+                    if let Err(e) = doorbell::ota::ota_from_url(&url) {
+                        log::error!("OTA Error: {e}");
+                    }
+                })?;
+                Ok("OTA started".to_string())
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use doorbell::template::{format_utc, TemplateContext};
+use doorbell::wifi::{default_name, WifiState};
+
+// Ring history used to populate message templates
+struct RingInfo {
+    start: Option<Instant>,
+    duration: Duration,
+    day: u64,
+    count_today: usize,
+}
+
+static RING_INFO: Mutex<RingInfo> = Mutex::new(RingInfo {
+    start: None,
+    duration: Duration::ZERO,
+    day: 0,
+    count_today: 0,
+});
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Seconds since boot
+pub fn uptime() -> u64 {
+    (unsafe { esp_idf_svc::sys::esp_timer_get_time() } / 1_000_000) as u64
+}
+
+pub fn ring_start() {
+    if let Ok(mut info) = RING_INFO.lock() {
+        // Day rolls over at midnight UTC (counts since boot if SNTP not synced)
+        let day = unix_time() / 86400;
+        if day != info.day {
+            info.day = day;
+            info.count_today = 0;
+        }
+        info.count_today += 1;
+        info.start = Some(Instant::now());
+        info.duration = Duration::ZERO;
+    }
+}
+
+pub fn ring_stop() {
+    if let Ok(mut info) = RING_INFO.lock() {
+        if let Some(start) = info.start.take() {
+            info.duration = start.elapsed();
+        }
+    }
+}
+
+// (duration of current/last ring, rings today)
+pub fn ring_info() -> (Duration, usize) {
+    match RING_INFO.lock() {
+        Ok(info) => (
+            info.start.map(|s| s.elapsed()).unwrap_or(info.duration),
+            info.count_today,
+        ),
+        Err(_) => (Duration::ZERO, 0),
+    }
+}
+
+pub fn ring_context() -> TemplateContext {
+    let (duration, count_today) = ring_info();
+    let stddev = match crate::adc::ADC_STATS.get_cloned() {
+        Ok(Some(stats)) => format!("{:.4}", stats.stddev),
+        _ => String::new(),
+    };
+    let ip = match crate::WIFI_STATE.get_cloned() {
+        Ok(WifiState::Station(_, ip_info)) | Ok(WifiState::AP(_, ip_info)) => {
+            ip_info.ip.to_string()
+        }
+        _ => String::new(),
+    };
+    TemplateContext::new()
+        .with("device", default_name("Doorbell"))
+        .with("time", format_utc(unix_time()))
+        .with("duration", format!("{:.1}", duration.as_secs_f32()))
+        .with("count_today", count_today)
+        .with("stddev", stddev)
+        .with("channel", crate::pushover::ring_channel())
+        .with("ip", ip)
+}
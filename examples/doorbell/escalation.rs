@@ -0,0 +1,160 @@
+use esp_idf_svc::http::server::{EspHttpConnection, Request};
+
+use askama::Template;
+
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use doorbell::web::{FlashMsg, NavBar};
+
+use crate::led_task::LedMessage;
+use crate::mqtt::MqttTask;
+use crate::pushover::PushoverSender;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// Time acknowledgement is indicated before returning to Idle
+const ACK_HOLD: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckState {
+    Idle,
+    Pending(Instant),
+    Acknowledged(Instant),
+    Escalated,
+}
+
+impl AckState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AckState::Idle => "IDLE",
+            AckState::Pending(_) => "PENDING",
+            AckState::Acknowledged(_) => "ACKNOWLEDGED",
+            AckState::Escalated => "ESCALATED",
+        }
+    }
+}
+
+impl std::fmt::Display for AckState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+pub static ACK_STATE: Mutex<AckState> = Mutex::new(AckState::Idle);
+
+// Called on RingStart - a new ring always restarts the acknowledgement timer
+pub fn ring() -> anyhow::Result<()> {
+    ACK_STATE.replace(AckState::Pending(Instant::now()))?;
+    Ok(())
+}
+
+// Acknowledge current ring (returns false if there was nothing to acknowledge)
+pub fn acknowledge(source: &str) -> bool {
+    match ACK_STATE.lock() {
+        Ok(mut state) => match *state {
+            AckState::Pending(_) | AckState::Escalated => {
+                log::info!("Ring acknowledged: {source}");
+                *state = AckState::Acknowledged(Instant::now());
+                true
+            }
+            _ => false,
+        },
+        Err(e) => {
+            log::error!("ACK_STATE: Mutex Error: {e}");
+            false
+        }
+    }
+}
+
+// Watch ACK_STATE - forward changes to LED/MQTT and escalate if ring
+// not acknowledged within ack_timeout (0 disables escalation)
+pub fn escalation_task(
+    pushover: PushoverSender,
+    mqtt_task: MqttTask,
+    led_tx: mpsc::Sender<LedMessage>,
+) -> anyhow::Result<thread::JoinHandle<()>> {
+    let ack_timeout = Duration::from_secs(pushover.ack_timeout() as u64);
+    thread::Builder::new()
+        .stack_size(8192)
+        .spawn(move || {
+            let mut prev = AckState::Idle;
+            loop {
+                let state = ACK_STATE.get_cloned().unwrap_or(AckState::Idle);
+                if let AckState::Pending(start) = state {
+                    if !ack_timeout.is_zero() && start.elapsed() >= ack_timeout {
+                        log::info!("Ring not acknowledged after {ack_timeout:?}: escalating");
+                        let _ = ACK_STATE.replace(AckState::Escalated);
+                        if let Err(e) =
+                            pushover.send_escalation_msg(&crate::context::ring_context())
+                        {
+                            log::error!("Error sending escalation: {e}");
+                        }
+                        continue;
+                    }
+                }
+                if let AckState::Acknowledged(at) = state {
+                    if at.elapsed() >= ACK_HOLD {
+                        let _ = ACK_STATE.replace(AckState::Idle);
+                        continue;
+                    }
+                }
+                if state != prev {
+                    log::info!("AckState: {prev} -> {state}");
+                    let _ = led_tx.send(LedMessage::Ack(state));
+                    if let Err(e) = mqtt_task.ack_msg(state) {
+                        log::error!("Error publishing ack state: {e}");
+                    }
+                    prev = state;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        })
+        .map_err(|e| anyhow::anyhow!("escalation_task: {e}"))
+}
+
+// HTTP Handlers
+
+#[derive(Template)]
+#[template(path = "ack.html")]
+struct AckPage {
+    state: AckState,
+    pending: bool,
+    navbar: NavBar<'static>,
+}
+
+// Confirmation page (notification link) - acknowledging requires POST so link
+// prefetch/previews do not acknowledge the ring
+pub fn make_ack_page(
+    navbar: NavBar<'static>,
+) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static {
+    move |request| {
+        let state = ACK_STATE.get_cloned()?;
+        let ack_page = AckPage {
+            state,
+            pending: matches!(state, AckState::Pending(_) | AckState::Escalated),
+            navbar: navbar.clone(),
+        };
+        let mut response = request.into_response(200, Some("OK"), &[])?;
+        let html = ack_page.render()?;
+        response.write(html.as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    }
+}
+
+pub fn ack_handler(request: Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    let (level, message) = if acknowledge("web") {
+        ("success", "Ring acknowledged")
+    } else {
+        ("error", "No ring to acknowledge")
+    };
+    request.into_response(
+        302,
+        Some(message),
+        &[
+            ("Location", "/"),
+            ("Set-Cookie", &FlashMsg::cookie(level, message)?),
+        ],
+    )?;
+    Ok::<(), anyhow::Error>(())
+}
@@ -0,0 +1,181 @@
+use serde::Serialize;
+use serde_json::json;
+
+use doorbell::mqtt::StaticMqttManager;
+use doorbell::wifi::{sta_rssi, WifiState};
+
+use crate::mqtt::MqttConfig;
+
+// Home Assistant MQTT discovery - https://www.home-assistant.io/integrations/mqtt/#mqtt-discovery
+
+#[derive(Serialize, Debug)]
+pub struct SensorState {
+    pub mean: f32,
+    pub stddev: f32,
+    pub threshold: f32,
+    pub threshold_multiplier: f32,
+    pub dnd: bool,
+    pub rssi: Option<i8>,
+    pub uptime: u64,
+}
+
+pub fn sensor_state() -> SensorState {
+    let (mean, stddev, threshold) = match crate::adc::ADC_STATS.get_cloned() {
+        Ok(Some(stats)) => (stats.mean, stats.stddev, stats.threshold),
+        _ => (0.0, 0.0, 0.0),
+    };
+    SensorState {
+        mean,
+        stddev,
+        threshold,
+        threshold_multiplier: crate::adc::threshold_multiplier(),
+        dnd: crate::command::DND.load(std::sync::atomic::Ordering::Relaxed),
+        rssi: sta_rssi(),
+        uptime: crate::context::uptime(),
+    }
+}
+
+// Discovery node_id must only contain [a-zA-Z0-9_-]
+fn node_id(config: &MqttConfig) -> String {
+    config
+        .client_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// (component, object_id, config) - null config clears entity
+fn entities(
+    config: &MqttConfig,
+    node_id: &str,
+) -> Vec<(&'static str, &'static str, serde_json::Value)> {
+    let state_topic = config.topic("state");
+    let sensor = |name: &str, key: &str, unit: Option<&str>, class: Option<&str>| {
+        let mut v = json!({
+            "name": name,
+            "unique_id": format!("{node_id}_{key}"),
+            "state_topic": state_topic,
+            "value_template": format!("{{{{ value_json.{key} }}}}"),
+            "state_class": "measurement",
+            "entity_category": "diagnostic",
+        });
+        if let Some(unit) = unit {
+            v["unit_of_measurement"] = json!(unit);
+        }
+        if let Some(class) = class {
+            v["device_class"] = json!(class);
+        }
+        v
+    };
+    vec![
+        (
+            "binary_sensor",
+            "ring",
+            json!({
+                "name": "Ring",
+                "unique_id": format!("{node_id}_ring"),
+                // Ring topic payloads are user templates so use the
+                // ring_event JSON instead
+                "state_topic": config.topic("ring_event"),
+                "value_template": "{{ 'ON' if value_json.ring else 'OFF' }}",
+                "icon": "mdi:doorbell",
+            }),
+        ),
+        ("sensor", "stddev", sensor("Std Dev", "stddev", None, None)),
+        ("sensor", "mean", sensor("Mean", "mean", None, None)),
+        (
+            "sensor",
+            "threshold",
+            sensor("Threshold", "threshold", None, None),
+        ),
+        (
+            "sensor",
+            "rssi",
+            sensor("RSSI", "rssi", Some("dBm"), Some("signal_strength")),
+        ),
+        (
+            "sensor",
+            "uptime",
+            sensor("Uptime", "uptime", Some("s"), Some("duration")),
+        ),
+        (
+            "button",
+            "reboot",
+            // Only if reboot command is allowed
+            if config.command_allowed("reboot") {
+                json!({
+                    "name": "Reboot",
+                    "unique_id": format!("{node_id}_reboot"),
+                    "command_topic": config.topic("cmd/reboot"),
+                    "device_class": "restart",
+                    "entity_category": "config",
+                })
+            } else {
+                serde_json::Value::Null
+            },
+        ),
+        (
+            "number",
+            "threshold_multiplier",
+            json!({
+                "name": "Threshold Multiplier",
+                "unique_id": format!("{node_id}_threshold_multiplier"),
+                "command_topic": config.topic("cmd/threshold"),
+                "state_topic": state_topic,
+                "value_template": "{{ value_json.threshold_multiplier }}",
+                "min": 1.0,
+                "max": 20.0,
+                "step": 0.1,
+                "mode": "box",
+                "entity_category": "config",
+            }),
+        ),
+    ]
+}
+
+// Publish retained discovery configs (or clear them if discovery disabled or
+// entity not available)
+pub fn publish_discovery(config: &MqttConfig) -> anyhow::Result<()> {
+    let node_id = node_id(config);
+    let mut device = json!({
+        "identifiers": [node_id],
+        "name": crate::NAVBAR.title,
+        "model": crate::MODEL,
+        "sw_version": crate::BUILD_INFO.build_hash,
+    });
+    if let Ok(WifiState::Station(_, ip_info)) = crate::WIFI_STATE.get_cloned() {
+        device["configuration_url"] = json!(format!("http://{}/", ip_info.ip));
+    }
+    for (component, object_id, mut entity) in entities(config, &node_id) {
+        let topic = format!(
+            "{}/{component}/{node_id}/{object_id}/config",
+            config.ha_prefix
+        );
+        let payload = if config.ha_discovery && !entity.is_null() {
+            entity["device"] = device.clone();
+            let (topic, online, offline) = config.availability();
+            entity["availability_topic"] = json!(topic);
+            entity["payload_available"] = json!(online);
+            entity["payload_not_available"] = json!(offline);
+            serde_json::to_vec(&entity)?
+        } else {
+            Vec::new()
+        };
+        StaticMqttManager::publish(&topic, &payload, config.status_qos(), true)?;
+    }
+    log::info!(
+        "HA discovery: {}",
+        if config.ha_discovery {
+            "published"
+        } else {
+            "cleared"
+        }
+    );
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use doorbell::mqtt::StaticMqttManager;
+
+use crate::escalation::AckState;
+use crate::mqtt::MqttConfig;
+
+// Homie convention - https://homieiot.github.io/specification/spec-core-v4_0_0/
+//
+// $state is used as the MQTT availability topic when Homie is enabled so the
+// LWT sets 'lost' if the connection is dropped (see MqttConfig::availability)
+
+pub const HOMIE_VERSION: &str = "4.0";
+
+pub const STATE_INIT: &str = "init";
+pub const STATE_READY: &str = "ready";
+pub const STATE_LOST: &str = "lost";
+
+struct Property {
+    id: &'static str,
+    name: &'static str,
+    datatype: &'static str,
+    format: Option<&'static str>,
+    unit: Option<&'static str>,
+    settable: bool,
+}
+
+struct Node {
+    id: &'static str,
+    name: &'static str,
+    node_type: &'static str,
+    properties: &'static [Property],
+}
+
+const NODES: [Node; 2] = [
+    Node {
+        id: "doorbell",
+        name: "Doorbell",
+        node_type: "doorbell",
+        properties: &[
+            Property {
+                id: "ring",
+                name: "Ring",
+                datatype: "boolean",
+                format: None,
+                unit: None,
+                settable: false,
+            },
+            Property {
+                id: "ack",
+                name: "Acknowledgement",
+                datatype: "enum",
+                format: Some("IDLE,PENDING,ACKNOWLEDGED,ESCALATED"),
+                unit: None,
+                settable: false,
+            },
+        ],
+    },
+    Node {
+        id: "adc",
+        name: "ADC",
+        node_type: "sensor",
+        properties: &[
+            Property {
+                id: "mean",
+                name: "Mean",
+                datatype: "float",
+                format: None,
+                unit: None,
+                settable: false,
+            },
+            Property {
+                id: "stddev",
+                name: "Std Dev",
+                datatype: "float",
+                format: None,
+                unit: None,
+                settable: false,
+            },
+            Property {
+                id: "threshold",
+                name: "Threshold",
+                datatype: "float",
+                format: None,
+                unit: None,
+                settable: false,
+            },
+            Property {
+                id: "multiplier",
+                name: "Threshold Multiplier",
+                datatype: "float",
+                format: Some("1:20"),
+                unit: None,
+                settable: true,
+            },
+        ],
+    },
+];
+
+// Homie device ID must only contain [a-z0-9-]
+fn device_id(config: &MqttConfig) -> String {
+    config
+        .client_id
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+fn base_topic(config: &MqttConfig) -> String {
+    format!("{}/{}", config.homie_prefix, device_id(config))
+}
+
+pub fn state_topic(config: &MqttConfig) -> String {
+    format!("{}/$state", base_topic(config))
+}
+
+fn publish(config: &MqttConfig, topic: &str, value: &str) -> anyhow::Result<u32> {
+    StaticMqttManager::publish(topic, value.as_bytes(), config.status_qos(), true)
+}
+
+// Publish device description ($state is set to 'init' while this is in progress
+// and 'ready' is published by MqttManager on connect)
+pub fn publish_description(config: &MqttConfig) -> anyhow::Result<()> {
+    if !config.homie {
+        return Ok(());
+    }
+    let base = base_topic(config);
+    publish(config, &state_topic(config), STATE_INIT)?;
+    publish(config, &format!("{base}/$homie"), HOMIE_VERSION)?;
+    publish(config, &format!("{base}/$name"), crate::NAVBAR.title)?;
+    let nodes = NODES.iter().map(|n| n.id).collect::<Vec<_>>().join(",");
+    publish(config, &format!("{base}/$nodes"), &nodes)?;
+    for node in NODES.iter() {
+        let node_topic = format!("{base}/{}", node.id);
+        publish(config, &format!("{node_topic}/$name"), node.name)?;
+        publish(config, &format!("{node_topic}/$type"), node.node_type)?;
+        let properties = node
+            .properties
+            .iter()
+            .map(|p| p.id)
+            .collect::<Vec<_>>()
+            .join(",");
+        publish(config, &format!("{node_topic}/$properties"), &properties)?;
+        for property in node.properties {
+            let property_topic = format!("{node_topic}/{}", property.id);
+            publish(config, &format!("{property_topic}/$name"), property.name)?;
+            publish(
+                config,
+                &format!("{property_topic}/$datatype"),
+                property.datatype,
+            )?;
+            if let Some(format) = property.format {
+                publish(config, &format!("{property_topic}/$format"), format)?;
+            }
+            if let Some(unit) = property.unit {
+                publish(config, &format!("{property_topic}/$unit"), unit)?;
+            }
+            if property.settable {
+                publish(config, &format!("{property_topic}/$settable"), "true")?;
+            }
+        }
+    }
+    publish_values(config)?;
+    log::info!("Homie description published: {base}");
+    Ok(())
+}
+
+// Subscribe to settable properties
+pub fn subscribe(config: &MqttConfig) -> anyhow::Result<()> {
+    if !config.homie {
+        return Ok(());
+    }
+    let topic = format!("{}/adc/multiplier/set", base_topic(config));
+    let handler_config = config.clone();
+    StaticMqttManager::subscribe_with_handler(&topic, config.command_qos(), move |_, data, _| {
+        let value = String::from_utf8_lossy(data);
+        match value
+            .trim()
+            .parse::<f32>()
+            .map_err(anyhow::Error::from)
+            .and_then(crate::adc::set_threshold_multiplier)
+        {
+            Ok(_) => log::info!("Homie: multiplier set: {value}"),
+            Err(e) => log::error!("Homie: invalid multiplier: {value} [{e}]"),
+        }
+        let _ = publish_values(&handler_config);
+    })
+}
+
+// Publish current ADC values
+pub fn publish_values(config: &MqttConfig) -> anyhow::Result<()> {
+    if !config.homie {
+        return Ok(());
+    }
+    let base = base_topic(config);
+    let state = crate::ha::sensor_state();
+    publish(config, &format!("{base}/adc/mean"), &state.mean.to_string())?;
+    publish(
+        config,
+        &format!("{base}/adc/stddev"),
+        &state.stddev.to_string(),
+    )?;
+    publish(
+        config,
+        &format!("{base}/adc/threshold"),
+        &state.threshold.to_string(),
+    )?;
+    publish(
+        config,
+        &format!("{base}/adc/multiplier"),
+        &state.threshold_multiplier.to_string(),
+    )?;
+    Ok(())
+}
+
+pub fn publish_ring(config: &MqttConfig, ring: bool) -> anyhow::Result<()> {
+    if config.homie {
+        publish(
+            config,
+            &format!("{}/doorbell/ring", base_topic(config)),
+            if ring { "true" } else { "false" },
+        )?;
+    }
+    Ok(())
+}
+
+pub fn publish_ack(config: &MqttConfig, state: AckState) -> anyhow::Result<()> {
+    if config.homie {
+        publish(
+            config,
+            &format!("{}/doorbell/ack", base_topic(config)),
+            state.as_str(),
+        )?;
+    }
+    Ok(())
+}
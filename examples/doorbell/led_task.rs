@@ -4,15 +4,19 @@ use std::time::Duration;
 
 use doorbell::ws2812::{colour, Rgb, Ws2812RmtSingle};
 
+use crate::escalation::AckState;
+
 pub enum LedMessage {
     Ring(bool),
     Flash(Rgb),
+    Ack(AckState),
 }
 
 pub fn led_task(mut led: Ws2812RmtSingle, led_rx: mpsc::Receiver<LedMessage>) {
     let mut ring = false;
     let mut timeout: Option<u8> = None;
     let mut on = false;
+    let mut ack = AckState::Idle;
     loop {
         match led_rx.try_recv() {
             Ok(LedMessage::Ring(v)) => {
@@ -32,11 +36,23 @@ pub fn led_task(mut led: Ws2812RmtSingle, led_rx: mpsc::Receiver<LedMessage>) {
                     led.set(colour::OFF).unwrap();
                 }
             }
+            Ok(LedMessage::Ack(state)) => {
+                log::info!(">> led_rx: {state}");
+                ack = state;
+            }
             Err(_e) => {}
         }
         // log::info!("ring={ring} timeout={timeout:?} on={on}");
-        led.set(if ring && on { colour::RED } else { colour::OFF })
-            .unwrap();
+        // Unacknowledged ring continues flashing (YELLOW/RED if escalated) -
+        // acknowledgement is shown GREEN until state returns to Idle
+        led.set(match (ring, ack, on) {
+            (true, _, true) => colour::RED,
+            (false, AckState::Acknowledged(_), _) => colour::GREEN,
+            (false, AckState::Pending(_), true) => colour::YELLOW,
+            (false, AckState::Escalated, true) => colour::RED,
+            _ => colour::OFF,
+        })
+        .unwrap();
         on = !on;
 
         timeout = match timeout {
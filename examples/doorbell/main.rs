@@ -1,38 +1,46 @@
 #![feature(lock_value_accessors)]
 
-use esp_idf_hal::gpio::OutputPin;
+use esp_idf_hal::gpio::{IOPin, OutputPin};
 use esp_idf_hal::task::watchdog::{TWDTConfig, TWDTDriver};
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::hal::prelude::*;
 use esp_idf_svc::http::Method;
 use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sntp::EspSntp;
 use esp_idf_svc::wifi::EspWifi;
 
 use std::sync::{mpsc, Mutex};
 use std::thread;
 use std::time::Duration;
 
+use doorbell::button::button_closure;
+use doorbell::mqtt::MqttLogger;
 use doorbell::nvs::NVStore;
 use doorbell::ota::Ota;
+use doorbell::template;
 use doorbell::web::{BuildInfo, HomePage, NavBar, NavLink, WebServer};
-use doorbell::wifi::{APConfig, APStore, WifiManager, WifiState};
+use doorbell::wifi::{FallbackAP, WifiManager, WifiState};
 use doorbell::ws2812::{colour, RgbLayout, Ws2812RmtSingle};
 
 mod adc;
+mod command;
+mod context;
+mod escalation;
+mod ha;
+mod homie;
 mod led_task;
+mod mdns;
 mod mqtt;
-mod mqtt_debug;
 mod pushover;
-
-pub use mqtt_debug::mqtt_debug;
-
-const AP_SSID: &str = "ESP32C3-AP";
-const AP_PASSWORD: &str = "password";
+mod shadow;
+mod telemetry;
 
 const NVS_NAMESPACE: &str = "DOORBELL";
 
 const WATCHDOG_TIMEOUT: u64 = 60;
 
+pub const MODEL: &str = "ESP32C3-Zero Doorbell";
+
 const BUILD_INFO: BuildInfo = BuildInfo {
     build_ts: env!("BUILD_TS"),
     build_branch: env!("BUILD_BRANCH"),
@@ -78,8 +86,8 @@ fn main() -> anyhow::Result<()> {
     // implemented by esp-idf-sys might not link properly. See https://github.com/esp-rs/esp-idf-template/issues/71
     esp_idf_svc::sys::link_patches();
 
-    // Bind the log crate to the ESP Logging facilities
-    esp_idf_svc::log::EspLogger::initialize_default();
+    // Bind the log crate to the ESP Logging facilities (and forward to MQTT if enabled)
+    MqttLogger::initialize()?;
     log::info!("Starting...");
 
     // Initialise peripherals
@@ -99,12 +107,18 @@ fn main() -> anyhow::Result<()> {
     let nvs = NVStore::init(nvs_default_partition.clone(), NVS_NAMESPACE)?;
 
     // WiFi
-    let mut wifi = WifiManager::new(EspWifi::new(
+    let wifi = WifiManager::new(EspWifi::new(
         peripherals.modem,
         sys_loop.clone(),
         Some(nvs_default_partition.clone()),
     )?)?;
 
+    // SNTP (used for Pushover recipient schedules)
+    let _sntp = EspSntp::new_default()?;
+
+    // mDNS (<hostname>.local)
+    mdns::init()?;
+
     // Onboard WS2812 (GPIO10)
     let ws2812 = peripherals.pins.gpio10.downgrade_output();
     let channel = peripherals.rmt.channel0;
@@ -134,6 +148,9 @@ fn main() -> anyhow::Result<()> {
     // ADC Task
     let (adc_tx, adc_rx) = mpsc::channel();
 
+    // Allow remote commands to inject test ring
+    command::init(adc_tx.clone())?;
+
     let _adc_task_id = adc::adc_task(
         peripherals.timer00,
         peripherals.adc1,
@@ -152,116 +169,114 @@ fn main() -> anyhow::Result<()> {
     mqtt_task.add_handlers(&mut web, NAVBAR)?;
 
     // Pushover
-    let mut pushover = pushover::PushoverSender::new()?;
+    let pushover = pushover::PushoverSender::new()?;
     pushover.add_handlers(&mut web, NAVBAR)?;
 
+    // Ring acknowledgement/escalation
+    let _escalation_task_id =
+        escalation::escalation_task(pushover.clone(), mqtt_task.clone(), led_tx.clone())?;
+    web.add_handler("/ack", Method::Get, escalation::make_ack_page(NAVBAR))?;
+    web.add_handler("/ack", Method::Post, escalation::ack_handler)?;
+
+    // Message template preview
+    web.add_handler("/template/preview", Method::Post, template::preview_handler)?;
+
+    // Onboard BOOT button (GPIO9) acknowledges ring
+    let button = peripherals.pins.gpio9.downgrade();
+    let _button_task_id = thread::Builder::new().stack_size(4096).spawn(move || {
+        button_closure(
+            button,
+            Some(|| {
+                escalation::acknowledge("button");
+            }),
+            None::<fn()>,
+            None,
+        )
+    })?;
+
+    // WiFi connection thread (publishes WifiState changes)
+    let wifi_rx = doorbell::wifi::subscribe();
+    let roam_rx = doorbell::wifi::roam::subscribe();
+    // Fallback AP (per-device SSID/password - eg. Doorbell-A1B2)
+    wifi.start(&sys_loop, Some(FallbackAP::load(NAVBAR.title)?))?;
+    let mut mqtt_started = false;
+
     // Start watchdog after initialisation
     let mut watchdog = twdt_driver.watch_current_task()?;
-    let mut count = 0_usize;
 
     loop {
-        let wifi_state = WIFI_STATE.get_cloned()?;
-        let wifi_connected = wifi.is_connected()?;
-        log::info!("{wifi_state} :: {wifi_connected}");
-
-        match (&wifi_state, wifi_connected) {
-            (WifiState::NotConnected, _) => {
-                // NotConnected - try to connect to known AP (or start local AP)
-                led_tx.send(led_task::LedMessage::Flash(colour::GREEN))?;
-                wifi.scan()?;
-                let wifi_state = wifi.try_connect(
-                    &APStore::get_aps()?,
-                    Some(APConfig::new(AP_SSID, AP_PASSWORD)?),
-                    30_000,
-                )?;
-                log::info!("WifiState: {wifi_state:?}");
-
-                // If we have connected start services
-                if let WifiState::Station(_, _) = wifi_state {
-                    // Start services
+        // WiFi state changes
+        while let Ok(wifi_state) = wifi_rx.try_recv() {
+            // Update WIFI_STATE (before starting services which use IP)
+            WIFI_STATE.replace(wifi_state.clone())?;
+
+            // Start services on first connection (MQTT reconnects itself)
+            if let WifiState::Station(_, _) = wifi_state {
+                if !mqtt_started {
                     log::info!("Starting mqtt_task:");
                     mqtt_task.run()?;
-                    mqtt_task.ring_msg(false)?;
+                    mqtt_task.ring_msg(false, &context::ring_context())?;
+                    mqtt_started = true;
                 }
+            }
+        }
 
-                // Update home page status
-                home_page.set_status(wifi_state.display_fields())?;
-
-                // Update WIFI_STATE
-                WIFI_STATE.replace(wifi_state)?;
-
-                // Update watchdog
-                watchdog.feed()?;
+        // Roam events (WiFi state is republished after roaming)
+        while let Ok(event) = roam_rx.try_recv() {
+            if mqtt_started {
+                if let Err(e) = mqtt_task.roam_msg(event) {
+                    log::error!("Failed to publish roam event: {e}");
+                }
             }
-            (WifiState::Station(ref ap, _), false) => {
-                // WiFi disconnected - try to reconnect (every 5 secs)
-                if count.is_multiple_of(5) {
-                    log::error!("WIFi Disconnected: Attempting to reconnect");
-                    match wifi.connect_sta(ap, 30_000) {
-                        Ok(WifiState::Station(config, ip_info)) => {
-                            let wifi_state = WifiState::Station(config, ip_info);
-                            log::info!("WIFi Reconnected: {wifi_state}");
-                            // Update home page status
-                            home_page.set_status(wifi_state.display_fields())?;
-                            // Update WIFI_STATE
-                            WIFI_STATE.replace(wifi_state)?;
-                        }
-                        Ok(_) => {
-                            log::info!("WiFi Failed to Reconnect");
-                        }
-                        Err(e) => {
-                            // Something went wrong - possibly reboot?
-                            log::info!("WiFi Error Reconnecting: {e}");
-                        }
+        }
+
+        // Ring detection continues while WiFi is down (MQTT messages are
+        // queued until reconnected)
+        match adc_rx.recv_timeout(Duration::from_millis(1000)) {
+            Ok(msg) => match msg {
+                adc::RingMessage::RingStart(ref _s) => {
+                    log::info!("adc_rx :: {msg:?}");
+
+                    led_tx.send(led_task::LedMessage::Ring(true))?;
+                    escalation::ring()?;
+                    context::ring_start();
+                    let ctx = context::ring_context();
+                    if mqtt_started {
+                        mqtt_task.ring_msg(true, &ctx)?;
+                        mqtt_task.event_msg(true)?;
                     }
+                    pushover.send_ring_msg(&ctx)?;
                 }
+                adc::RingMessage::RingStop => {
+                    log::info!("adc_rx :: {msg:?}");
+                    led_tx.send(led_task::LedMessage::Ring(false))?;
+                    context::ring_stop();
+                    if mqtt_started {
+                        mqtt_task.ring_msg(false, &context::ring_context())?;
+                        mqtt_task.event_msg(false)?;
+                    }
+                }
+            },
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(e) => log::error!("ERROR :: adc_rx :: {e}"),
+        }
 
-                // Flush adc_rx buffer
-                while adc_rx.try_recv().is_ok() {}
-                led_tx.send(led_task::LedMessage::Flash(colour::RED))?;
-                thread::sleep(Duration::from_millis(1000));
+        // Refresh home page status (RSSI)
+        let wifi_state = WIFI_STATE.get_cloned()?;
+        home_page.set_status(wifi_state.display_fields())?;
 
-                // We dont update watchdog here - timeout and reset if we dont re-connect
-            }
-            (WifiState::Station(_, _), true) => {
-                // WiFi Online
-                match adc_rx.recv_timeout(Duration::from_millis(1000)) {
-                    Ok(msg) => match msg {
-                        adc::RingMessage::RingStart(ref _s) => {
-                            log::info!("adc_rx :: {msg:?}");
-
-                            led_tx.send(led_task::LedMessage::Ring(true))?;
-                            mqtt_task.ring_msg(true)?;
-                            mqtt_task.stats_msg()?;
-                            pushover.send_ring_msg()?;
-                        }
-                        adc::RingMessage::RingStop => {
-                            log::info!("adc_rx :: {msg:?}");
-                            led_tx.send(led_task::LedMessage::Ring(false))?;
-                            mqtt_task.ring_msg(false)?;
-                        }
-                    },
-                    Err(mpsc::RecvTimeoutError::Timeout) => {}
-                    Err(e) => log::error!("ERROR :: adc_rx :: {e}"),
-                }
-                led_tx.send(led_task::LedMessage::Flash(colour::BLUE))?;
+        let colour = match wifi_state {
+            WifiState::Station(_, _) => colour::BLUE,
+            WifiState::NotConnected => colour::RED,
+            WifiState::AP(_, _) => colour::GREEN,
+        };
+        led_tx.send(led_task::LedMessage::Flash(colour))?;
 
-                // Update watchdog
-                watchdog.feed()?;
-            }
-            (WifiState::AP(_, _), _) => {
-                // AP Mode
-                // Flush adc_rx buffer
-                while adc_rx.try_recv().is_ok() {}
-                thread::sleep(Duration::from_millis(1000));
-                led_tx.send(led_task::LedMessage::Flash(colour::GREEN))?;
-
-                // Update watchdog
-                watchdog.feed()?;
-            }
+        if let Err(e) = mdns::update() {
+            log::error!("mDNS Error: {e}");
         }
 
-        // Update counter
-        count += 1;
+        // Update watchdog
+        watchdog.feed()?;
     }
 }
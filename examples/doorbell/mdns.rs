@@ -0,0 +1,48 @@
+use std::sync::Mutex;
+
+use doorbell::mqtt::StaticMqttManager;
+use doorbell::wifi::{default_name, Mdns};
+
+// mDNS services - web UI (_http._tcp) and device (_doorbell._tcp) with TXT
+// records for firmware, model and MQTT status
+
+const HTTP_PORT: u16 = 80;
+
+static MQTT_STATUS: Mutex<&str> = Mutex::new("");
+
+fn mqtt_status() -> &'static str {
+    match StaticMqttManager::status() {
+        Ok(status) if status.connected => "connected",
+        Ok(_) => "disconnected",
+        Err(_) => "disabled",
+    }
+}
+
+pub fn init() -> anyhow::Result<()> {
+    // Default hostname from MAC (eg. doorbell-a1b2)
+    Mdns::init(&default_name("doorbell"))?;
+    Mdns::add_service("_http", "_tcp", HTTP_PORT, &[("path", "/")])?;
+    let status = mqtt_status();
+    Mdns::add_service(
+        "_doorbell",
+        "_tcp",
+        HTTP_PORT,
+        &[
+            ("firmware", crate::BUILD_INFO.build_hash),
+            ("model", crate::MODEL),
+            ("mqtt", status),
+        ],
+    )?;
+    MQTT_STATUS.replace(status)?;
+    Ok(())
+}
+
+// Update MQTT status TXT record (if changed)
+pub fn update() -> anyhow::Result<()> {
+    let status = mqtt_status();
+    if MQTT_STATUS.replace(status)? != status {
+        log::info!("mDNS: mqtt={status}");
+        Mdns::set_txt("_doorbell", "_tcp", "mqtt", status)?;
+    }
+    Ok(())
+}
@@ -7,11 +7,24 @@ use esp_idf_svc::http::Method;
 use askama::Template;
 use serde::{Deserialize, Serialize};
 
-use doorbell::mqtt::{check_mqtt_url, MqttMessage, StaticMqttManager};
-use doorbell::nvs::NVStore;
-use doorbell::web::{FlashMsg, NavBar, WebServer};
+use doorbell::mqtt::{
+    check_mqtt_url, parse_level, qos_from_level, MessageProperties, MqttLogger, MqttMessage,
+    MqttOptions, MqttStatus, QoS, StaticMqttManager, AVAILABILITY_OFFLINE, AVAILABILITY_ONLINE,
+    MQTT_QUEUE_SIZE,
+};
+use doorbell::nvs::{NVStore, NV_STORE_MAX};
+use doorbell::template::{self, TemplateContext};
+use doorbell::web::{read_body, FlashMsg, NavBar, WebServer};
+use doorbell::wifi::{default_name, RoamEvent};
 
-#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+use crate::command::{Command, CommandResponse, COMMANDS};
+use crate::escalation::AckState;
+use crate::ha;
+use crate::homie;
+use crate::shadow::{self, ShadowConfig, REDACTED};
+use crate::telemetry;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct MqttConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -19,8 +32,384 @@ pub struct MqttConfig {
     pub client_id: String,
     pub ring_topic: String,
     pub status_topic: String,
+    #[serde(default = "default_ring_on_payload")]
+    pub ring_on_payload: String,
+    #[serde(default = "default_ring_off_payload")]
+    pub ring_off_payload: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default)]
+    pub crt_bundle: bool,
+    #[serde(default)]
+    pub availability_topic: String,
+    #[serde(default = "default_publish_interval")]
+    pub publish_interval: u32,
+    #[serde(default = "default_ring_qos")]
+    pub ring_qos: u8,
+    #[serde(default)]
+    pub status_qos: u8,
+    #[serde(default = "default_command_qos")]
+    pub command_qos: u8,
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    #[serde(default)]
+    pub mqtt5: bool,
+    #[serde(default = "default_ring_expiry")]
+    pub ring_expiry: u32,
+    #[serde(default)]
+    pub log_enabled: bool,
+    #[serde(default)]
+    pub log_topic: String,
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    #[serde(default = "default_log_rate")]
+    pub log_rate: u32,
+    #[serde(default = "default_allowed_commands")]
+    pub allowed_commands: String,
+    #[serde(default)]
+    pub ha_discovery: bool,
+    #[serde(default = "default_ha_prefix")]
+    pub ha_prefix: String,
+    #[serde(default)]
+    pub homie: bool,
+    #[serde(default = "default_homie_prefix")]
+    pub homie_prefix: String,
+}
+
+const MIN_PUBLISH_INTERVAL: u32 = 5;
+const MAX_QUEUE_SIZE: usize = 256;
+
+fn default_publish_interval() -> u32 {
+    30
+}
+
+// Ring events/acknowledgements and commands default to QoS 1
+fn default_ring_qos() -> u8 {
+    1
+}
+
+fn default_command_qos() -> u8 {
+    1
+}
+
+// Ring message expiry in secs (v5 only - 0 disables)
+fn default_ring_expiry() -> u32 {
+    300
+}
+
+fn default_queue_size() -> usize {
+    MQTT_QUEUE_SIZE
+}
+
+const LOG_LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+// Max forwarded log messages/sec
+fn default_log_rate() -> u32 {
+    5
+}
+
+// Reboot/OTA must be explicitly enabled
+fn default_allowed_commands() -> String {
+    "test_ring,adc_debug,threshold,dnd,log".to_string()
+}
+
+fn default_ha_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+fn default_homie_prefix() -> String {
+    "homie".to_string()
+}
+
+fn default_ring_on_payload() -> String {
+    "ON".to_string()
+}
+
+fn default_ring_off_payload() -> String {
+    "OFF".to_string()
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            client_id: String::new(),
+            ring_topic: String::new(),
+            status_topic: String::new(),
+            ring_on_payload: default_ring_on_payload(),
+            ring_off_payload: default_ring_off_payload(),
+            username: String::new(),
+            password: String::new(),
+            crt_bundle: false,
+            availability_topic: String::new(),
+            publish_interval: default_publish_interval(),
+            ring_qos: default_ring_qos(),
+            status_qos: 0,
+            command_qos: default_command_qos(),
+            queue_size: default_queue_size(),
+            mqtt5: false,
+            ring_expiry: default_ring_expiry(),
+            log_enabled: false,
+            log_topic: String::new(),
+            log_level: default_log_level(),
+            log_rate: default_log_rate(),
+            allowed_commands: default_allowed_commands(),
+            ha_discovery: false,
+            ha_prefix: default_ha_prefix(),
+            homie: false,
+            homie_prefix: default_homie_prefix(),
+        }
+    }
+}
+
+// Uploaded certificates: (kind, NVS key, label)
+const MQTT_CERTS: [(&str, &str, &str); 3] = [
+    ("ca", "mqtt_ca", "CA Certificate"),
+    ("cert", "mqtt_cert", "Client Certificate"),
+    ("key", "mqtt_key", "Client Key"),
+];
+
+fn get_cert(kind: &str) -> anyhow::Result<Option<String>> {
+    match MQTT_CERTS.iter().find(|(k, _, _)| *k == kind) {
+        Some((_, key, _)) => NVStore::get::<String>(key),
+        None => Ok(None),
+    }
+}
+
+impl MqttConfig {
+    pub fn options(&self) -> anyhow::Result<MqttOptions> {
+        let (availability_topic, online, offline) = self.availability();
+        Ok(MqttOptions {
+            client_id: Some(self.client_id.clone()),
+            availability_topic: Some(availability_topic),
+            availability_payloads: Some((online.to_string(), offline.to_string())),
+            username: (!self.username.is_empty()).then(|| self.username.clone()),
+            password: (!self.username.is_empty()).then(|| self.password.clone()),
+            crt_bundle: self.crt_bundle,
+            ca_cert: get_cert("ca")?,
+            client_cert: get_cert("cert")?,
+            client_key: get_cert("key")?,
+            queue_size: Some(self.queue_size),
+            mqtt5: self.mqtt5,
+            user_properties: vec![
+                ("device_id".to_string(), default_name("Doorbell")),
+                (
+                    "firmware".to_string(),
+                    crate::BUILD_INFO.build_hash.to_string(),
+                ),
+            ],
+        })
+    }
+
+    // Ring messages expire if not delivered (v5 only)
+    fn ring_properties(&self) -> MessageProperties {
+        MessageProperties {
+            message_expiry: (self.ring_expiry > 0).then_some(self.ring_expiry),
+            ..Default::default()
+        }
+    }
+
+    // QoS levels are checked when config is saved
+    pub fn ring_qos(&self) -> QoS {
+        qos_from_level(self.ring_qos).unwrap_or(QoS::AtLeastOnce)
+    }
+
+    pub fn status_qos(&self) -> QoS {
+        qos_from_level(self.status_qos).unwrap_or(QoS::AtMostOnce)
+    }
+
+    pub fn command_qos(&self) -> QoS {
+        qos_from_level(self.command_qos).unwrap_or(QoS::AtLeastOnce)
+    }
+
+    pub fn topic(&self, suffix: &str) -> String {
+        format!("{}/{suffix}", self.status_topic)
+    }
+
+    // Defaults to <status_topic>/availability
+    pub fn availability_topic(&self) -> String {
+        if self.availability_topic.is_empty() {
+            self.topic("availability")
+        } else {
+            self.availability_topic.clone()
+        }
+    }
+
+    // (topic, online, offline) - Homie uses $state (ready/lost)
+    pub fn availability(&self) -> (String, &'static str, &'static str) {
+        if self.homie {
+            (
+                homie::state_topic(self),
+                homie::STATE_READY,
+                homie::STATE_LOST,
+            )
+        } else {
+            (
+                self.availability_topic(),
+                AVAILABILITY_ONLINE,
+                AVAILABILITY_OFFLINE,
+            )
+        }
+    }
+
+    // Defaults to <status_topic>/log
+    pub fn log_topic(&self) -> String {
+        if self.log_topic.is_empty() {
+            self.topic("log")
+        } else {
+            self.log_topic.clone()
+        }
+    }
+
+    fn subscriptions(&self) -> Vec<String> {
+        vec![self.topic("ack/set"), self.topic("cmd/+")]
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        template::validate(&self.ring_on_payload)
+            .and_then(|_| template::validate(&self.ring_off_payload))
+            .map_err(|e| anyhow::anyhow!("Invalid Payload: {e}"))?;
+        for level in [self.ring_qos, self.status_qos, self.command_qos] {
+            qos_from_level(level)?;
+        }
+        if self.publish_interval < MIN_PUBLISH_INTERVAL {
+            anyhow::bail!("Telemetry interval must be >= {MIN_PUBLISH_INTERVAL}s");
+        }
+        parse_level(&self.log_level)?;
+        if self.queue_size == 0 || self.queue_size > MAX_QUEUE_SIZE {
+            anyhow::bail!("Queue size must be 1-{MAX_QUEUE_SIZE}");
+        }
+        if let Some(cmd) = self
+            .allowed_commands
+            .split(',')
+            .map(str::trim)
+            .find(|cmd| !cmd.is_empty() && !COMMANDS.contains(cmd))
+        {
+            anyhow::bail!("Invalid Command: {cmd} (valid: {})", COMMANDS.join(","));
+        }
+        if !check_mqtt_url(&self.url) {
+            anyhow::bail!("Invalid MQTT URL");
+        }
+        Ok(())
+    }
+
+    pub fn command_allowed(&self, command: &str) -> bool {
+        self.allowed_commands
+            .split(',')
+            .any(|c| c.trim() == command)
+    }
+}
+
+fn handle_message(config: &MqttConfig, topic: &str, data: &str, properties: &MessageProperties) {
+    if topic == config.topic("ack/set") {
+        crate::escalation::acknowledge("mqtt");
+    } else if let Some(name) = topic.strip_prefix(&config.topic("cmd/")) {
+        let result = if config.command_allowed(name) {
+            Command::parse(name, data).and_then(Command::execute)
+        } else {
+            Err(anyhow::anyhow!("Command not allowed: {name}"))
+        };
+        log::info!("MQTT Command: {name} [{data}] -> {result:?}");
+        let response = CommandResponse {
+            command: name,
+            ok: result.is_ok(),
+            message: match result {
+                Ok(m) => m,
+                Err(e) => e.to_string(),
+            },
+        };
+        // Use request response topic/correlation data if provided (v5)
+        let response_topic = properties
+            .response_topic
+            .clone()
+            .unwrap_or_else(|| config.topic("response"));
+        let response_properties = MessageProperties {
+            correlation_data: properties.correlation_data.clone(),
+            ..Default::default()
+        };
+        if let Err(e) = serde_json::to_vec(&response)
+            .map_err(anyhow::Error::from)
+            .and_then(|r| {
+                StaticMqttManager::publish_with_properties(
+                    &response_topic,
+                    &r,
+                    config.status_qos(),
+                    false,
+                    &response_properties,
+                )
+            })
+        {
+            log::error!("Failed to publish command response: {e}");
+        }
+        // Update state (threshold/dnd etc.)
+        let _ = publish_state(config);
+    }
+}
+
+// Periodic JSON telemetry (see telemetry.rs)
+fn publish_telemetry(config: &MqttConfig) {
+    let docs = [
+        ("stats", telemetry::adc().to_vec()),
+        ("wifi", telemetry::wifi().to_vec()),
+        ("system", telemetry::system().to_vec()),
+    ];
+    for (topic, doc) in docs {
+        if let Err(e) = doc.and_then(|d| {
+            StaticMqttManager::publish(&config.topic(topic), &d, config.status_qos(), false)
+        }) {
+            log::error!("Failed to publish telemetry: {topic} [{e}]");
+        }
+    }
+    let _ = publish_state(config);
+    let _ = homie::publish_values(config);
 }
 
+// JSON sensor state (used by Home Assistant entities)
+fn publish_state(config: &MqttConfig) -> anyhow::Result<u32> {
+    let state = serde_json::to_vec(&ha::sensor_state())?;
+    StaticMqttManager::publish(&config.topic("state"), &state, config.status_qos(), false)
+}
+
+impl ShadowConfig for MqttConfig {
+    fn load() -> anyhow::Result<Self> {
+        Ok(NVStore::get("mqtt")?.unwrap_or_default())
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        MqttConfig::validate(self)
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        NVStore::set::<MqttConfig>("mqtt", self)
+    }
+
+    fn redact(&mut self) {
+        if !self.password.is_empty() {
+            self.password = REDACTED.to_string();
+        }
+    }
+
+    fn restore_secrets(&mut self, current: &Self) {
+        if self.password == REDACTED {
+            self.password = current.password.clone();
+        }
+    }
+
+    // Prevent retained desired config widening the command allow-list or
+    // redirecting the connection
+    fn protected() -> &'static [&'static str] {
+        &["url", "username", "password", "allowed_commands"]
+    }
+}
+
+#[derive(Clone)]
 pub struct MqttTask(MqttConfig);
 
 impl MqttTask {
@@ -30,81 +419,159 @@ impl MqttTask {
 
     pub fn run(&self) -> anyhow::Result<()> {
         if self.0.enabled {
-            let mqtt_rx = StaticMqttManager::init(&self.0.url, Some(&self.0.client_id))?;
+            let mqtt_rx = StaticMqttManager::init(&self.0.url, &self.0.options()?)?;
 
             log::info!("Starting MQTT Connection Thread");
-            let _connection_t = thread::spawn(move || loop {
-                match mqtt_rx.recv_timeout(Duration::from_secs(2)) {
-                    Ok(MqttMessage::Reconnected) => {
-                        log::info!("MQTT re-connected: resubscribing");
-                        // Re-subscribe channels here
+            let _connection_t = {
+                let config = self.0.clone();
+                thread::spawn(move || loop {
+                    // Subscriptions/availability are restored by MqttManager
+                    match mqtt_rx.recv_timeout(Duration::from_secs(2)) {
+                        Ok(MqttMessage::Reconnected) => {
+                            log::info!("MQTT re-connected");
+                            if let Err(e) = ha::publish_discovery(&config) {
+                                log::error!("Failed to publish HA discovery: {e}");
+                            }
+                        }
+                        Ok(MqttMessage::Message(topic, _)) => {
+                            log::info!("mqtt_rx: unhandled message: {topic}");
+                        }
+                        _ => {}
                     }
-                    Ok(MqttMessage::Message(topic, data)) => {
-                        let data = String::from_utf8_lossy(&data).to_string();
+                })
+            };
+
+            // Homie description (sets $state to init until online published)
+            if let Err(e) = homie::publish_description(&self.0) {
+                log::error!("Failed to publish Homie description: {e}");
+            }
+
+            // Availability (LWT sets offline)
+            StaticMqttManager::publish_online()?;
+
+            // Subscribe to acknowledgement/command topics (failures are
+            // logged - subscriptions are retried by MqttManager on reconnect)
+            for topic in self.0.subscriptions() {
+                let config = self.0.clone();
+                if let Err(e) = StaticMqttManager::subscribe_with_handler(
+                    &topic,
+                    self.0.command_qos(),
+                    move |topic, data, properties| {
+                        let data = String::from_utf8_lossy(data);
                         log::info!("mqtt_rx: {topic} : {data}");
-                    }
-                    _ => {}
+                        handle_message(&config, topic, &data, properties);
+                    },
+                ) {
+                    log::error!("Failed to subscribe: {topic} [{e}]");
                 }
-            });
+            }
+
+            if let Err(e) = homie::subscribe(&self.0) {
+                log::error!("Failed to subscribe to Homie topics: {e}");
+            }
+
+            // Remote configuration
+            if let Err(e) = shadow::subscribe(&self.0) {
+                log::error!("Failed to subscribe to desired config: {e}");
+            }
+            if let Err(e) = shadow::publish_reported(&self.0) {
+                log::error!("Failed to publish reported config: {e}");
+            }
 
-            let wifi_topic = format!("{}/wifi", self.0.status_topic);
-            let stats_topic = format!("{}/stats", self.0.status_topic);
+            // Home Assistant discovery
+            if let Err(e) = ha::publish_discovery(&self.0) {
+                log::error!("Failed to publish HA discovery: {e}");
+            }
+
+            let config = self.0.clone();
+            let interval =
+                Duration::from_secs(self.0.publish_interval.max(MIN_PUBLISH_INTERVAL) as u64);
             log::info!("Starting MQTT Status Thread");
             let _update_t = thread::spawn(move || loop {
-                if let Ok(wifi_state) = crate::WIFI_STATE.try_lock() {
-                    let _ = StaticMqttManager::publish(
-                        &wifi_topic,
-                        wifi_state.to_string().as_bytes(),
-                        false,
-                    );
-                }
-                if let Ok(stats) = crate::adc::ADC_STATS.try_lock() {
-                    if let Some(ref stats) = *stats {
-                        let _ = StaticMqttManager::publish(
-                            &stats_topic,
-                            stats.to_string().as_bytes(),
-                            false,
-                        );
-                    }
-                }
-                thread::sleep(Duration::from_secs(30));
+                publish_telemetry(&config);
+                thread::sleep(interval);
             });
+
+            // Log forwarding (can be toggled at runtime with log command)
+            log::info!("Starting MQTT Log Thread");
+            MqttLogger::start(
+                &self.0.log_topic(),
+                parse_level(&self.0.log_level)?,
+                self.0.log_rate,
+            )?;
+            MqttLogger::set_enabled(self.0.log_enabled);
         }
         Ok(())
     }
 
-    pub fn ring_msg(&self, state: bool) -> anyhow::Result<u32> {
+    pub fn ring_msg(&self, state: bool, ctx: &TemplateContext) -> anyhow::Result<u32> {
         if self.0.enabled {
             log::info!("ring_msg: {state}");
-            StaticMqttManager::publish(
-                &self.0.ring_topic,
+            let payload = template::render(
                 if state {
-                    "ON".as_bytes()
+                    &self.0.ring_on_payload
                 } else {
-                    "OFF".as_bytes()
+                    &self.0.ring_off_payload
                 },
+                ctx,
+            )?;
+            homie::publish_ring(&self.0, state)?;
+            StaticMqttManager::publish_with_properties(
+                &self.0.ring_topic,
+                payload.as_bytes(),
+                self.0.ring_qos(),
                 true,
+                &self.0.ring_properties(),
             )
         } else {
             Ok(0)
         }
     }
 
-    pub fn stats_msg(&self) -> anyhow::Result<u32> {
+    pub fn ack_msg(&self, state: AckState) -> anyhow::Result<u32> {
         if self.0.enabled {
-            let stats_topic = format!("{}/ring_stats", self.0.status_topic);
-            if let Ok(stats) = crate::adc::ADC_STATS.try_lock() {
-                if let Some(ref stats) = *stats {
-                    log::info!("stats_msg: {}", stats);
-                    return StaticMqttManager::publish(
-                        &stats_topic,
-                        stats.to_string().as_bytes(),
-                        false,
-                    );
-                }
-            }
+            homie::publish_ack(&self.0, state)?;
+            let ack_topic = self.0.topic("ack");
+            StaticMqttManager::publish(
+                &ack_topic,
+                state.as_str().as_bytes(),
+                self.0.ring_qos(),
+                true,
+            )
+        } else {
+            Ok(0)
+        }
+    }
+
+    pub fn event_msg(&self, state: bool) -> anyhow::Result<u32> {
+        if self.0.enabled {
+            let event = telemetry::ring_event(state);
+            log::info!("event_msg: {event:?}");
+            StaticMqttManager::publish_with_properties(
+                &self.0.topic("ring_event"),
+                &event.to_vec()?,
+                self.0.ring_qos(),
+                false,
+                &self.0.ring_properties(),
+            )
+        } else {
+            Ok(0)
+        }
+    }
+
+    pub fn roam_msg(&self, event: RoamEvent) -> anyhow::Result<u32> {
+        if self.0.enabled {
+            let event = telemetry::roam_event(event);
+            log::info!("roam_msg: {event:?}");
+            StaticMqttManager::publish(
+                &self.0.topic("wifi/roam"),
+                &event.to_vec()?,
+                self.0.status_qos(),
+                false,
+            )
+        } else {
+            Ok(0)
         }
-        Ok(0)
     }
 
     pub fn add_handlers(
@@ -114,6 +581,8 @@ impl MqttTask {
     ) -> anyhow::Result<()> {
         server.add_handler("/mqtt", Method::Get, mqtt_handler(&navbar))?;
         server.add_handler("/mqtt", Method::Post, mqtt_submit)?;
+        server.add_handler("/mqtt/cert", Method::Post, cert_upload_handler)?;
+        server.add_handler("/mqtt/cert/delete/*", Method::Get, cert_delete_handler)?;
         Ok(())
     }
 }
@@ -123,6 +592,9 @@ impl MqttTask {
 struct MqttPage<'a> {
     title: &'a str,
     config: MqttConfig,
+    certs: Vec<(&'a str, &'a str, bool)>,
+    status: Option<MqttStatus>,
+    log_levels: &'a [&'a str],
     navbar: NavBar<'static>,
 }
 
@@ -132,9 +604,16 @@ pub fn mqtt_handler(
     let navbar = navbar.clone();
     move |request| {
         let mqtt_config = NVStore::get("mqtt")?.unwrap_or_default();
+        let certs = MQTT_CERTS
+            .iter()
+            .map(|(kind, _, label)| Ok((*kind, *label, get_cert(kind)?.is_some())))
+            .collect::<anyhow::Result<Vec<_>>>()?;
         let mqtt_page = MqttPage {
             title: "MQTT Settings",
             config: mqtt_config,
+            certs,
+            status: StaticMqttManager::status().ok(),
+            log_levels: &LOG_LEVELS,
             navbar: navbar.clone(),
         };
         let mut response = request.into_response(200, Some("OK"), &[])?;
@@ -144,25 +623,34 @@ pub fn mqtt_handler(
     }
 }
 
+// Form only field (password is otherwise kept if left empty)
+#[derive(Deserialize)]
+struct ClearPasswordForm {
+    #[serde(default)]
+    clear_password: bool,
+}
+
 pub fn mqtt_submit(mut request: Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
-    let mut buf = [0_u8; 1024];
-    let len = request.read(&mut buf)?;
+    let body = read_body(&mut request, 2048)?;
 
-    match serde_urlencoded::from_bytes::<MqttConfig>(&buf[0..len]) {
-        Ok(c) => {
-            log::info!("MQTT Config: >>{c:?}");
+    match serde_urlencoded::from_bytes::<MqttConfig>(&body)
+        .and_then(|c| Ok((c, serde_urlencoded::from_bytes::<ClearPasswordForm>(&body)?)))
+    {
+        Ok((mut c, form)) => {
+            log::info!("MQTT Config: >>{} [{}]", c.url, c.client_id);
+            // Password field is not displayed - keep existing if not changed
+            if c.password.is_empty() && !c.username.is_empty() && !form.clear_password {
+                let prev: MqttConfig = NVStore::get("mqtt")?.unwrap_or_default();
+                c.password = prev.password;
+            }
             // Check config
-            if !check_mqtt_url(&c.url) {
-                let flash = serde_json::to_string(&FlashMsg {
-                    level: "error",
-                    message: "Invalid MQTT URL",
-                })?;
+            if let Err(e) = c.validate() {
                 request.into_response(
                     302,
                     Some("Error updating MQTT settings"),
                     &[
                         ("Location", "/mqtt"),
-                        ("Set-Cookie", &format!("flash_msg={flash}; path=/")),
+                        ("Set-Cookie", &FlashMsg::cookie("error", &e.to_string())?),
                     ],
                 )?;
                 return Ok::<(), anyhow::Error>(());
@@ -200,3 +688,61 @@ pub fn mqtt_submit(mut request: Request<&mut EspHttpConnection>) -> anyhow::Resu
     }
     Ok::<(), anyhow::Error>(())
 }
+
+#[derive(Deserialize)]
+struct CertUpload {
+    kind: String,
+    pem: String,
+}
+
+pub fn cert_upload_handler(mut request: Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    // Allow for urlencoding overhead (stored PEM is limited by NVStore)
+    let result = read_body(&mut request, 2 * NV_STORE_MAX).and_then(|body| {
+        let upload = serde_urlencoded::from_bytes::<CertUpload>(&body)?;
+        let (_, key, label) = MQTT_CERTS
+            .iter()
+            .find(|(k, _, _)| *k == upload.kind)
+            .ok_or_else(|| anyhow::anyhow!("Invalid certificate type: {}", upload.kind))?;
+        let pem = upload.pem.trim().replace("\r\n", "\n");
+        if !pem.starts_with("-----BEGIN ") {
+            anyhow::bail!("Invalid PEM data");
+        }
+        NVStore::set::<String>(key, &pem)?;
+        Ok(*label)
+    });
+    let (level, message) = match result {
+        Ok(label) => ("success", format!("Successfully saved {label}")),
+        Err(e) => ("error", format!("Failed to save certificate [{e}]")),
+    };
+    log::info!("{level}: {message}");
+    request.into_response(
+        302,
+        Some(&message),
+        &[
+            ("Location", "/mqtt"),
+            ("Set-Cookie", &FlashMsg::cookie(level, &message)?),
+        ],
+    )?;
+    Ok::<(), anyhow::Error>(())
+}
+
+pub fn cert_delete_handler(request: Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    let kind = request.uri().split('/').next_back().expect("Invalid Kind");
+    let (level, message) = match MQTT_CERTS.iter().find(|(k, _, _)| *k == kind) {
+        Some((_, key, label)) => match NVStore::delete(key) {
+            Ok(_) => ("success", format!("Deleted {label}")),
+            Err(e) => ("error", format!("Error deleting {label} [{e}]")),
+        },
+        None => ("error", format!("Invalid certificate type: {kind}")),
+    };
+    log::info!("{level}: {message}");
+    request.into_response(
+        302,
+        Some(&message),
+        &[
+            ("Location", "/mqtt"),
+            ("Set-Cookie", &FlashMsg::cookie(level, &message)?),
+        ],
+    )?;
+    Ok::<(), anyhow::Error>(())
+}
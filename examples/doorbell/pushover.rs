@@ -7,19 +7,47 @@ use esp_idf_svc::http::Method;
 use askama::Template;
 use serde::{Deserialize, Serialize};
 
+use std::sync::mpsc;
+use std::thread;
+
 use doorbell::nvs::NVStore;
-use doorbell::web::{FlashMsg, WebServer};
+use doorbell::template::{self, TemplateContext};
+use doorbell::web::{read_body, FlashMsg, WebServer};
+use doorbell::wifi::WifiState;
 
+use crate::shadow::{ShadowConfig, REDACTED};
 use crate::NavBar;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
-struct PushoverConfig {
+pub struct PushoverConfig {
     #[serde(default)]
     enabled: bool,
     url: String,
     token: String,
-    user: String,
     ring_message: String,
+    #[serde(default)]
+    ack_timeout: u32,
+    // Channel name for this doorbell - each device belongs to a single
+    // channel which is matched against the recipient channel lists (so
+    // recipients can be routed per doorbell when several are monitored)
+    #[serde(default = "default_channel")]
+    channel: String,
+    // Local time offset from UTC for recipient schedules (mins)
+    #[serde(default)]
+    utc_offset: i32,
+    #[serde(default)]
+    recipients: Vec<Recipient>,
+    // Legacy single user/escalation keys (migrated to recipients)
+    #[serde(default, skip_serializing)]
+    user: String,
+    #[serde(default, skip_serializing)]
+    escalation_user: String,
+}
+
+const DEFAULT_CHANNEL: &str = "doorbell";
+
+fn default_channel() -> String {
+    DEFAULT_CHANNEL.to_string()
 }
 
 impl Default for PushoverConfig {
@@ -27,36 +55,244 @@ impl Default for PushoverConfig {
         Self {
             url: "https://api.pushover.net/1/messages.json".to_string(),
             token: String::new(),
-            user: String::new(),
             ring_message: "DOORBELL".to_string(),
+            ack_timeout: 0,
+            channel: default_channel(),
+            utc_offset: 0,
+            recipients: Vec::new(),
+            user: String::new(),
+            escalation_user: String::new(),
             enabled: false,
         }
     }
 }
 
+impl PushoverConfig {
+    fn load() -> anyhow::Result<Self> {
+        let mut config: Self = NVStore::get("pushover")?.unwrap_or_default();
+        // Convert legacy user/escalation_user keys to recipients
+        if config.recipients.is_empty() {
+            if !config.user.is_empty() {
+                config.recipients.push(Recipient {
+                    name: "default".to_string(),
+                    user: config.user.clone(),
+                    ..Default::default()
+                });
+            }
+            for (i, user) in config
+                .escalation_user
+                .split(',')
+                .map(str::trim)
+                .filter(|u| !u.is_empty())
+                .enumerate()
+            {
+                config.recipients.push(Recipient {
+                    name: format!("escalation{i}"),
+                    user: user.to_string(),
+                    escalation: true,
+                    ..Default::default()
+                });
+            }
+        }
+        Ok(config)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        template::validate(&self.ring_message)
+            .map_err(|e| anyhow::anyhow!("Invalid Message: {e}"))?;
+        if self.channel.trim().is_empty() || self.channel.contains(',') {
+            anyhow::bail!("Invalid Channel: {}", self.channel);
+        }
+        if !(-720..=840).contains(&self.utc_offset) {
+            anyhow::bail!("Invalid UTC Offset: {} (-720 to 840 mins)", self.utc_offset);
+        }
+        for recipient in &self.recipients {
+            recipient
+                .validate()
+                .map_err(|e| anyhow::anyhow!("Invalid Recipient: {} [{e}]", recipient.name))?;
+        }
+        Ok(())
+    }
+}
+
+impl ShadowConfig for PushoverConfig {
+    fn load() -> anyhow::Result<Self> {
+        PushoverConfig::load()
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        PushoverConfig::validate(self)
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        NVStore::set::<PushoverConfig>("pushover", self)
+    }
+
+    fn redact(&mut self) {
+        if !self.token.is_empty() {
+            self.token = REDACTED.to_string();
+        }
+        for recipient in self.recipients.iter_mut() {
+            recipient.user = REDACTED.to_string();
+        }
+    }
+
+    // Recipient user keys are matched by name
+    fn restore_secrets(&mut self, current: &Self) {
+        if self.token == REDACTED {
+            self.token = current.token.clone();
+        }
+        for recipient in self.recipients.iter_mut() {
+            if recipient.user == REDACTED {
+                recipient.user = current
+                    .recipients
+                    .iter()
+                    .find(|r| r.name == recipient.name)
+                    .map(|r| r.user.clone())
+                    .unwrap_or_default();
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+struct Recipient {
+    name: String,
+    user: String,
+    #[serde(default)]
+    device: String,
+    #[serde(default)]
+    priority: i8,
+    // Active window "HH:MM-HH:MM" (local time) - empty for always
+    #[serde(default)]
+    schedule: String,
+    // Comma separated list of channels - empty for all
+    #[serde(default)]
+    channels: String,
+    // Only notified when ring is escalated
+    #[serde(default)]
+    escalation: bool,
+}
+
+impl Recipient {
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.name.is_empty() || self.user.is_empty() {
+            anyhow::bail!("Name and User Key required");
+        }
+        if !(-2..=2).contains(&self.priority) {
+            anyhow::bail!("Invalid priority: {}", self.priority);
+        }
+        parse_schedule(&self.schedule)?;
+        Ok(())
+    }
+
+    fn matches(&self, channel: &str, escalation: bool, utc_offset: i32) -> bool {
+        self.escalation == escalation
+            && (self.channels.trim().is_empty()
+                || self.channels.split(',').any(|c| c.trim() == channel))
+            && in_schedule(&self.schedule, utc_offset)
+    }
+}
+
+// Parse "HH:MM-HH:MM" schedule into (start, end) minutes since midnight
+fn parse_schedule(schedule: &str) -> anyhow::Result<Option<(u32, u32)>> {
+    if schedule.trim().is_empty() {
+        return Ok(None);
+    }
+    let minutes = |t: &str| -> anyhow::Result<u32> {
+        let (h, m) = t
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Invalid time: {t}"))?;
+        let (h, m) = (h.parse::<u32>()?, m.parse::<u32>()?);
+        if h > 23 || m > 59 {
+            anyhow::bail!("Invalid time: {t}");
+        }
+        Ok(h * 60 + m)
+    };
+    let (start, end) = schedule
+        .split_once('-')
+        .ok_or_else(|| anyhow::anyhow!("Invalid schedule: {schedule}"))?;
+    Ok(Some((minutes(start)?, minutes(end)?)))
+}
+
+fn in_schedule(schedule: &str, utc_offset: i32) -> bool {
+    match (parse_schedule(schedule), local_minutes(utc_offset)) {
+        (Ok(Some((start, end))), Some(now)) => {
+            if start <= end {
+                (start..end).contains(&now)
+            } else {
+                // Window wraps midnight
+                now >= start || now < end
+            }
+        }
+        // Always deliver if no schedule or clock not set
+        _ => true,
+    }
+}
+
+// Current local minutes since midnight (None if SNTP has not synced)
+fn local_minutes(utc_offset: i32) -> Option<u32> {
+    const MIN_VALID_TS: u64 = 1_700_000_000;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    (now > MIN_VALID_TS).then_some(((now / 60) as i64 + utc_offset as i64).rem_euclid(1440) as u32)
+}
+
+// Channel name used for notification routing
+pub fn ring_channel() -> String {
+    PushoverConfig::load()
+        .map(|c| c.channel)
+        .unwrap_or_else(|_| default_channel())
+}
+
 #[derive(Serialize, Debug)]
 struct PushoverMessage<'a> {
     token: &'a str,
     user: &'a str,
     message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<i8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expire: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url_title: Option<&'a str>,
 }
 
-pub struct PushoverSender {
-    config: PushoverConfig,
+// Queued notification (sent by worker thread so the HTTP requests do not
+// delay the ring path)
+struct Notification {
+    channel: String,
+    escalation: bool,
+    message: String,
 }
 
-impl PushoverSender {
-    pub fn new() -> anyhow::Result<Self> {
-        Ok(Self {
-            config: NVStore::get("pushover")?.unwrap_or_default(),
-        })
-    }
-    pub fn send_ring_msg(&mut self) -> anyhow::Result<()> {
-        let ring_message = self.config.ring_message.clone();
-        self.send(&ring_message)
+impl PushoverConfig {
+    fn deliver(&self, notification: &Notification) {
+        let ack_url = ack_url();
+        for recipient in self.recipients.iter().filter(|r| {
+            r.matches(
+                &notification.channel,
+                notification.escalation,
+                self.utc_offset,
+            )
+        }) {
+            // Try all recipients even if one fails
+            if let Err(e) = self.post(recipient, &notification.message, ack_url.as_deref()) {
+                log::error!("Error sending Pushover message to {}: {e}", recipient.name);
+            }
+        }
     }
-    pub fn send(&mut self, msg: &str) -> anyhow::Result<()> {
-        if self.config.enabled {
+    fn post(&self, recipient: &Recipient, msg: &str, ack_url: Option<&str>) -> anyhow::Result<()> {
+        if self.enabled {
             // Create client for each request as otherwise can panic
             // if network connection dropped
             let http_config = HttpConfiguration {
@@ -66,11 +302,23 @@ impl PushoverSender {
             let mut client = HttpClient::wrap(EspHttpConnection::new(&http_config)?);
 
             let payload = PushoverMessage {
-                token: &self.config.token,
-                user: &self.config.user,
+                token: &self.token,
+                user: &recipient.user,
                 message: msg,
+                device: (!recipient.device.is_empty()).then_some(recipient.device.as_str()),
+                priority: (recipient.priority != 0).then_some(recipient.priority),
+                // Emergency priority requires retry/expire
+                retry: (recipient.priority == 2).then_some(60),
+                expire: (recipient.priority == 2).then_some(3600),
+                url: ack_url,
+                url_title: ack_url.map(|_| "Acknowledge"),
             };
-            log::info!("Sending Pushover message: {payload:?}");
+            // Token/user key are not logged (logs may be forwarded to MQTT)
+            log::info!(
+                "Sending Pushover message: {} [{}]",
+                recipient.name,
+                payload.message
+            );
 
             // Convert to JSON
             let payload = serde_json::to_vec(&payload)?;
@@ -83,11 +331,11 @@ impl PushoverSender {
                 ("accept", "application/json"),
             ];
 
-            let mut request = client.post(&self.config.url, &headers)?;
+            let mut request = client.post(&self.url, &headers)?;
 
             request.write_all(&payload)?;
             request.flush()?;
-            log::info!("HTTP Request -> POST {}", self.config.url);
+            log::info!("HTTP Request -> POST {}", self.url);
 
             match request.submit() {
                 Ok(response) => log::info!("HTTP Response <- {}", response.status()),
@@ -99,6 +347,52 @@ impl PushoverSender {
             Ok(())
         }
     }
+}
+
+#[derive(Clone)]
+pub struct PushoverSender {
+    config: PushoverConfig,
+    tx: mpsc::Sender<Notification>,
+}
+
+impl PushoverSender {
+    pub fn new() -> anyhow::Result<Self> {
+        let config = PushoverConfig::load()?;
+        let (tx, rx) = mpsc::channel::<Notification>();
+        let worker = config.clone();
+        thread::Builder::new().stack_size(8192).spawn(move || {
+            while let Ok(notification) = rx.recv() {
+                worker.deliver(&notification);
+            }
+        })?;
+        Ok(Self { config, tx })
+    }
+    pub fn ack_timeout(&self) -> u32 {
+        self.config.ack_timeout
+    }
+    pub fn send_ring_msg(&self, ctx: &TemplateContext) -> anyhow::Result<()> {
+        let message = template::render(&self.config.ring_message, ctx)?;
+        self.send_all(ctx.get("channel").unwrap_or_default(), false, message)
+    }
+    pub fn send_escalation_msg(&self, ctx: &TemplateContext) -> anyhow::Result<()> {
+        let message = format!(
+            "{} (Not acknowledged)",
+            template::render(&self.config.ring_message, ctx)?
+        );
+        self.send_all(ctx.get("channel").unwrap_or_default(), true, message)
+    }
+    fn send_all(&self, channel: &str, escalation: bool, message: String) -> anyhow::Result<()> {
+        if crate::command::DND.load(std::sync::atomic::Ordering::Relaxed) {
+            log::info!("DND enabled: not sending Pushover message");
+            return Ok(());
+        }
+        self.tx.send(Notification {
+            channel: channel.to_string(),
+            escalation,
+            message,
+        })?;
+        Ok(())
+    }
     pub fn add_handlers(
         &self,
         server: &mut WebServer,
@@ -106,10 +400,20 @@ impl PushoverSender {
     ) -> anyhow::Result<()> {
         server.add_handler("/pushover", Method::Get, pushover_handler(&navbar))?;
         server.add_handler("/pushover", Method::Post, pushover_submit)?;
+        server.add_handler("/pushover/add", Method::Post, recipient_add_handler)?;
+        server.add_handler("/pushover/delete/*", Method::Get, recipient_delete_handler)?;
         Ok(())
     }
 }
 
+// Acknowledgement link included in ring notifications
+fn ack_url() -> Option<String> {
+    match crate::WIFI_STATE.get_cloned() {
+        Ok(WifiState::Station(_, ip_info)) => Some(format!("http://{}/ack", ip_info.ip)),
+        _ => None,
+    }
+}
+
 #[derive(askama::Template)]
 #[template(path = "pushover.html")]
 struct PushoverPage<'a> {
@@ -125,7 +429,7 @@ pub fn pushover_handler(
        + 'static {
     let navbar = navbar.clone();
     move |request| {
-        let pushover_config = NVStore::get("pushover")?.unwrap_or_default();
+        let pushover_config = PushoverConfig::load()?;
         let mqtt_page = PushoverPage {
             title: "Pushover Settings",
             config: pushover_config,
@@ -138,24 +442,56 @@ pub fn pushover_handler(
     }
 }
 
+// Settings form (excludes recipients)
+#[derive(Deserialize, Debug)]
+struct PushoverSettings {
+    #[serde(default)]
+    enabled: bool,
+    url: String,
+    token: String,
+    ring_message: String,
+    ack_timeout: u32,
+    channel: String,
+    utc_offset: i32,
+}
+
 pub fn pushover_submit(
     mut request: server::Request<&mut server::EspHttpConnection>,
 ) -> anyhow::Result<()> {
-    let mut buf = [0_u8; 1024];
-    let len = request.read(&mut buf)?;
+    let body = read_body(&mut request, 2048)?;
 
-    match serde_urlencoded::from_bytes::<PushoverConfig>(&buf[0..len]) {
+    match serde_urlencoded::from_bytes::<PushoverSettings>(&body) {
         Ok(c) => {
-            log::info!("MQTT Config: >>{c:?}");
-            // Update NVS
-            NVStore::set::<PushoverConfig>("pushover", &c)?;
+            log::info!("Pushover Config: >>{} [enabled: {}]", c.url, c.enabled);
+            // Update NVS (recipients are managed separately)
+            let mut config = PushoverConfig::load()?;
+            config.enabled = c.enabled;
+            config.url = c.url;
+            config.token = c.token;
+            config.ring_message = c.ring_message;
+            config.ack_timeout = c.ack_timeout;
+            config.channel = c.channel.trim().to_string();
+            config.utc_offset = c.utc_offset;
+            if let Err(e) = config.validate() {
+                let message = format!("Invalid Pushover settings: {e}");
+                request.into_response(
+                    302,
+                    Some("Error updating Pushover settings"),
+                    &[
+                        ("Location", "/pushover"),
+                        ("Set-Cookie", &FlashMsg::cookie("error", &message)?),
+                    ],
+                )?;
+                return Ok::<(), anyhow::Error>(());
+            }
+            NVStore::set::<PushoverConfig>("pushover", &config)?;
             let flash = serde_json::to_string(&FlashMsg {
                 level: "success",
                 message: "Successfully updated Pushover settings",
             })?;
             request.into_response(
                 302,
-                Some("Successfully updated Pushover MQTT settings"),
+                Some("Successfully updated Pushover settings"),
                 &[
                     ("Location", "/pushover"),
                     ("Set-Cookie", &format!("flash_msg={flash}; path=/")),
@@ -163,7 +499,7 @@ pub fn pushover_submit(
             )?;
         }
         Err(e) => {
-            log::info!("Error decoding MQTT config: {e}");
+            log::info!("Error decoding Pushover config: {e}");
             let flash = serde_json::to_string(&FlashMsg {
                 level: "error",
                 message: &format!("Error updating Pushover settings: {e}"),
@@ -180,3 +516,81 @@ pub fn pushover_submit(
     }
     Ok::<(), anyhow::Error>(())
 }
+
+pub fn recipient_add_handler(
+    mut request: server::Request<&mut server::EspHttpConnection>,
+) -> anyhow::Result<()> {
+    let body = read_body(&mut request, 1024)?;
+
+    match serde_urlencoded::from_bytes::<Recipient>(&body) {
+        Ok(recipient) => {
+            log::info!("Pushover Recipient: {}", recipient.name);
+            let result = recipient.validate().and_then(|_| {
+                let mut config = PushoverConfig::load()?;
+                // Replace existing recipient with same name
+                config.recipients.retain(|r| r.name != recipient.name);
+                config.recipients.push(recipient.clone());
+                NVStore::set::<PushoverConfig>("pushover", &config)
+            });
+            let (level, message) = match result {
+                Ok(_) => (
+                    "success",
+                    &format!("Successfully saved recipient: {}", recipient.name),
+                ),
+                Err(e) => (
+                    "error",
+                    &format!("Failed to save recipient: {} [{e}]", recipient.name),
+                ),
+            };
+            log::info!("{level}: {message}");
+            request.into_response(
+                302,
+                Some(message),
+                &[
+                    ("Location", "/pushover"),
+                    ("Set-Cookie", &FlashMsg::cookie(level, message)?),
+                ],
+            )?;
+        }
+        Err(_) => {
+            log::error!("Invalid form data");
+            request.into_response(400, Some("Invalid form data"), &[])?;
+        }
+    }
+    Ok::<(), anyhow::Error>(())
+}
+
+pub fn recipient_delete_handler(
+    request: server::Request<&mut server::EspHttpConnection>,
+) -> anyhow::Result<()> {
+    let name = request.uri().split('/').next_back().expect("Invalid Name");
+    let name = urlencoding::decode(name)?.into_owned();
+
+    let mut config = PushoverConfig::load()?;
+    let (level, message) = if config.recipients.iter().any(|r| r.name == name) {
+        config.recipients.retain(|r| r.name != name);
+        match NVStore::set::<PushoverConfig>("pushover", &config) {
+            Ok(_) => (
+                "success",
+                &format!("Successfully deleted recipient: {name}"),
+            ),
+            Err(e) => (
+                "error",
+                &format!("Error: Failed to delete recipient: {name} [{e}]"),
+            ),
+        }
+    } else {
+        ("error", &format!("Error: Invalid recipient {name}"))
+    };
+
+    log::info!("{level}: {message}");
+    request.into_response(
+        302,
+        Some(message),
+        &[
+            ("Location", "/pushover"),
+            ("Set-Cookie", &FlashMsg::cookie(level, message)?),
+        ],
+    )?;
+    Ok::<(), anyhow::Error>(())
+}
@@ -0,0 +1,234 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use std::thread;
+use std::time::Duration;
+
+use doorbell::mqtt::StaticMqttManager;
+use doorbell::nvs::NVStore;
+
+use crate::adc::AdcParams;
+use crate::mqtt::MqttConfig;
+use crate::pushover::PushoverConfig;
+
+// Remote configuration (device shadow)
+//
+// <status_topic>/config/desired  - retained desired config (set by fleet tool)
+// <status_topic>/config/reported - retained applied config (secrets redacted)
+// <status_topic>/config/error    - rejected desired config
+//
+// Desired config is {"version": N, "mqtt": {..}, "pushover": {..}, "adc": {..}}
+// where each section is optional and merged with the current config (RFC 7386
+// merge patch). Only versions greater than the last applied version are applied.
+// Security sensitive fields (eg. MQTT broker/credentials and the command
+// allow-list) are protected and can only be changed locally.
+
+pub const REDACTED: &str = "********";
+
+const SHADOW_NVS_KEY: &str = "shadow";
+const RESTART_DELAY: Duration = Duration::from_secs(2);
+
+// Config sections managed by shadow
+pub trait ShadowConfig: Serialize + DeserializeOwned {
+    fn load() -> anyhow::Result<Self>;
+    fn validate(&self) -> anyhow::Result<()>;
+    fn save(&self) -> anyhow::Result<()>;
+    // True if changes only take effect after restart
+    fn restart_required() -> bool {
+        true
+    }
+    // Replace secrets with REDACTED
+    fn redact(&mut self) {}
+    // Replace REDACTED values with current secrets
+    fn restore_secrets(&mut self, _current: &Self) {}
+    // Fields which can only be changed locally (rejected in desired config if
+    // different from current value)
+    fn protected() -> &'static [&'static str] {
+        &[]
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Desired {
+    version: u32,
+    #[serde(default)]
+    mqtt: Option<Value>,
+    #[serde(default)]
+    pushover: Option<Value>,
+    #[serde(default)]
+    adc: Option<Value>,
+}
+
+#[derive(Serialize, Debug)]
+struct Reported {
+    version: u32,
+    etag: String,
+    mqtt: MqttConfig,
+    pushover: PushoverConfig,
+    adc: AdcParams,
+}
+
+#[derive(Serialize, Debug)]
+struct ShadowError {
+    version: u32,
+    error: String,
+}
+
+fn merge(target: &mut Value, patch: &Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (k, v) in patch {
+                if v.is_null() {
+                    target.remove(k);
+                } else {
+                    merge(target.entry(k.clone()).or_insert(Value::Null), v);
+                }
+            }
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+// Merge and validate section (None if not present)
+fn prepare<T: ShadowConfig>(patch: &Option<Value>) -> anyhow::Result<Option<T>> {
+    match patch {
+        Some(patch) => {
+            let current = T::load()?;
+            let mut value = serde_json::to_value(&current)?;
+            // Protected fields may be present (eg. reported config echoed
+            // back) but must be unchanged
+            if let Some(field) = T::protected().iter().find(|f| {
+                patch
+                    .get(**f)
+                    .is_some_and(|v| v != REDACTED && Some(v) != value.get(**f))
+            }) {
+                anyhow::bail!("Field cannot be changed remotely: {field}");
+            }
+            merge(&mut value, patch);
+            let mut config: T = serde_json::from_value(value)?;
+            config.restore_secrets(&current);
+            config.validate()?;
+            Ok(Some(config))
+        }
+        None => Ok(None),
+    }
+}
+
+// FNV-1a hash of reported config
+fn etag(data: &[u8]) -> String {
+    let hash = data.iter().fold(0xcbf29ce484222325_u64, |h, b| {
+        (h ^ *b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+fn applied_version() -> u32 {
+    NVStore::get::<u32>(SHADOW_NVS_KEY)
+        .ok()
+        .flatten()
+        .unwrap_or(0)
+}
+
+// Validate all sections before saving any (returns true if restart required)
+fn apply(desired: &Desired) -> anyhow::Result<bool> {
+    let mqtt = prepare::<MqttConfig>(&desired.mqtt)?;
+    let pushover = prepare::<PushoverConfig>(&desired.pushover)?;
+    let adc = prepare::<AdcParams>(&desired.adc)?;
+    let mut restart = false;
+    if let Some(mqtt) = mqtt {
+        mqtt.save()?;
+        restart |= MqttConfig::restart_required();
+    }
+    if let Some(pushover) = pushover {
+        pushover.save()?;
+        restart |= PushoverConfig::restart_required();
+    }
+    if let Some(adc) = adc {
+        adc.save()?;
+        restart |= AdcParams::restart_required();
+    }
+    NVStore::set::<u32>(SHADOW_NVS_KEY, &desired.version)?;
+    Ok(restart)
+}
+
+fn redacted<T: ShadowConfig>() -> anyhow::Result<T> {
+    let mut config = T::load()?;
+    config.redact();
+    Ok(config)
+}
+
+pub fn publish_reported(config: &MqttConfig) -> anyhow::Result<()> {
+    let mut reported = Reported {
+        version: applied_version(),
+        etag: String::new(),
+        mqtt: redacted()?,
+        pushover: redacted()?,
+        adc: redacted()?,
+    };
+    reported.etag = etag(&serde_json::to_vec(&reported)?);
+    StaticMqttManager::publish(
+        &config.topic("config/reported"),
+        &serde_json::to_vec(&reported)?,
+        config.status_qos(),
+        true,
+    )?;
+    log::info!("Shadow reported: v{} [{}]", reported.version, reported.etag);
+    Ok(())
+}
+
+fn publish_error(config: &MqttConfig, version: u32, error: String) -> anyhow::Result<u32> {
+    log::error!("Shadow rejected: v{version} [{error}]");
+    let error = ShadowError { version, error };
+    StaticMqttManager::publish(
+        &config.topic("config/error"),
+        &serde_json::to_vec(&error)?,
+        config.status_qos(),
+        false,
+    )
+}
+
+pub fn handle_desired(config: &MqttConfig, data: &[u8]) {
+    // Empty payload clears retained desired config
+    if data.is_empty() {
+        return;
+    }
+    let desired = match serde_json::from_slice::<Desired>(data) {
+        Ok(desired) => desired,
+        Err(e) => {
+            let _ = publish_error(config, 0, format!("Invalid desired config: {e}"));
+            return;
+        }
+    };
+    let current = applied_version();
+    if desired.version <= current {
+        log::info!("Shadow: desired v{} already applied", desired.version);
+        return;
+    }
+    match apply(&desired) {
+        Ok(restart) => {
+            log::info!("Shadow: applied v{} (restart: {restart})", desired.version);
+            if let Err(e) = publish_reported(config) {
+                log::error!("Failed to publish reported config: {e}");
+            }
+            if restart {
+                thread::spawn(|| {
+                    thread::sleep(RESTART_DELAY);
+                    esp_idf_hal::reset::restart();
+                });
+            }
+        }
+        Err(e) => {
+            let _ = publish_error(config, desired.version, e.to_string());
+        }
+    }
+}
+
+pub fn subscribe(config: &MqttConfig) -> anyhow::Result<()> {
+    let handler_config = config.clone();
+    StaticMqttManager::subscribe_with_handler(
+        &config.topic("config/desired"),
+        config.command_qos(),
+        move |_, data, _| handle_desired(&handler_config, data),
+    )
+}
@@ -0,0 +1,157 @@
+use serde::Serialize;
+
+use doorbell::mqtt::{MqttLogger, MqttStatus, StaticMqttManager};
+use doorbell::template::format_utc;
+use doorbell::wifi::roam::{format_bssid, last_roam, roam_count};
+use doorbell::wifi::{
+    health, sta_bssid, sta_channel, sta_rssi, HealthStatus, RoamEvent, WifiState,
+};
+
+use crate::adc::Stats;
+
+// JSON telemetry documents - increment TELEMETRY_VERSION if format changes
+pub const TELEMETRY_VERSION: u32 = 1;
+
+#[derive(Serialize, Debug)]
+pub struct Telemetry<T: Serialize> {
+    pub version: u32,
+    pub uptime: u64,
+    #[serde(flatten)]
+    pub data: T,
+}
+
+impl<T: Serialize> Telemetry<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            version: TELEMETRY_VERSION,
+            uptime: crate::context::uptime(),
+            data,
+        }
+    }
+
+    pub fn to_vec(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RingEvent {
+    pub ring: bool,
+    pub channel: String,
+    pub time: String,
+    pub duration: f32,
+    pub count_today: usize,
+    pub stats: Option<Stats>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AdcTelemetry {
+    pub stats: Option<Stats>,
+    pub threshold_multiplier: f32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WifiTelemetry {
+    pub mode: &'static str,
+    pub ssid: Option<String>,
+    pub ip: Option<String>,
+    pub bssid: Option<String>,
+    pub rssi: Option<i8>,
+    pub channel: Option<u8>,
+    pub roams: u32,
+    pub last_roam: Option<RoamEvent>,
+    pub health: HealthStatus,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Firmware {
+    pub hash: &'static str,
+    pub branch: &'static str,
+    pub build_ts: &'static str,
+    pub profile: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SystemTelemetry {
+    pub free_heap: u32,
+    pub min_free_heap: u32,
+    pub reset_reason: String,
+    pub firmware: Firmware,
+    pub mqtt: Option<MqttStatus>,
+    pub log_dropped: u32,
+}
+
+pub fn ring_event(ring: bool) -> Telemetry<RingEvent> {
+    let (duration, count_today) = crate::context::ring_info();
+    Telemetry::new(RingEvent {
+        ring,
+        channel: crate::pushover::ring_channel(),
+        time: format_utc(crate::context::unix_time()),
+        duration: duration.as_secs_f32(),
+        count_today,
+        stats: crate::adc::ADC_STATS.get_cloned().ok().flatten(),
+    })
+}
+
+pub fn adc() -> Telemetry<AdcTelemetry> {
+    Telemetry::new(AdcTelemetry {
+        stats: crate::adc::ADC_STATS.get_cloned().ok().flatten(),
+        threshold_multiplier: crate::adc::threshold_multiplier(),
+    })
+}
+
+pub fn wifi() -> Telemetry<WifiTelemetry> {
+    let wifi_state = crate::WIFI_STATE
+        .get_cloned()
+        .unwrap_or(WifiState::NotConnected);
+    let (mode, ssid, ip) = match wifi_state {
+        WifiState::NotConnected => ("not_connected", None, None),
+        WifiState::Station(ap, ip_info) => (
+            "station",
+            Some(ap.ssid.to_string()),
+            Some(ip_info.ip.to_string()),
+        ),
+        WifiState::AP(ap, ip_info) => (
+            "ap",
+            Some(ap.ssid.to_string()),
+            Some(ip_info.ip.to_string()),
+        ),
+    };
+    Telemetry::new(WifiTelemetry {
+        mode,
+        ssid,
+        ip,
+        bssid: sta_bssid().map(|bssid| format_bssid(&bssid).to_string()),
+        rssi: sta_rssi(),
+        channel: sta_channel(),
+        roams: roam_count(),
+        last_roam: last_roam(),
+        health: health::status(),
+    })
+}
+
+pub fn roam_event(event: RoamEvent) -> Telemetry<RoamEvent> {
+    Telemetry::new(event)
+}
+
+pub fn system() -> Telemetry<SystemTelemetry> {
+    let (free_heap, min_free_heap) = unsafe {
+        (
+            esp_idf_svc::sys::esp_get_free_heap_size(),
+            esp_idf_svc::sys::esp_get_minimum_free_heap_size(),
+        )
+    };
+    Telemetry::new(SystemTelemetry {
+        free_heap,
+        min_free_heap,
+        reset_reason: format!("{:?}", esp_idf_hal::reset::ResetReason::get()),
+        firmware: Firmware {
+            hash: crate::BUILD_INFO.build_hash,
+            branch: crate::BUILD_INFO.build_branch,
+            build_ts: crate::BUILD_INFO.build_ts,
+            profile: crate::BUILD_INFO.build_profile,
+        },
+        mqtt: StaticMqttManager::status().ok(),
+        log_dropped: MqttLogger::dropped(),
+    })
+}
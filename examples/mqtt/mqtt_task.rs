@@ -7,7 +7,7 @@ use std::time::Duration;
 use askama::Template;
 use serde::{Deserialize, Serialize};
 
-use doorbell::mqtt::{check_mqtt_url, MqttMessage, StaticMqttManager};
+use doorbell::mqtt::{check_mqtt_url, MqttMessage, MqttOptions, QoS, StaticMqttManager};
 use doorbell::nvs::NVStore;
 use doorbell::web::{FlashMsg, NavBar, WebServer};
 
@@ -34,26 +34,25 @@ impl MQTTTask {
 
     pub fn run<F>(&self, f_ring: F, ip: &str) -> anyhow::Result<()>
     where
-        F: Fn(&str) + Send + 'static,
+        F: Fn(&str) + Send + Sync + 'static,
     {
         if self.0.enabled {
-            let mqtt_rx = StaticMqttManager::init(&self.0.url, Some(&self.0.client_id))?;
-            let ring_topic = self.0.ring_topic.clone();
-
+            let mqtt_rx = StaticMqttManager::init(
+                &self.0.url,
+                &MqttOptions {
+                    client_id: Some(self.0.client_id.clone()),
+                    ..Default::default()
+                },
+            )?;
             log::info!("Starting MQTT Connection Thread");
             let _connection_t = thread::spawn(move || loop {
                 match mqtt_rx.recv_timeout(Duration::from_secs(2)) {
-                    Ok(MqttMessage::Message(topic, data)) => {
-                        let data = String::from_utf8_lossy(&data).to_string();
-                        log::info!("mqtt_rx: {topic} : {data}");
-                        if topic == ring_topic {
-                            f_ring(data.as_str());
-                        }
+                    Ok(MqttMessage::Message(topic, _)) => {
+                        log::info!("mqtt_rx: unhandled message: {topic}");
                     }
                     Ok(MqttMessage::Reconnected) => {
-                        log::info!("MQTT re-connected: resubscribing");
-                        StaticMqttManager::subscribe(&ring_topic)
-                            .expect("Failed to resubscribe to MQTT_RING_TOPIC");
+                        // Subscriptions are restored by MqttManager
+                        log::info!("MQTT re-connected");
                     }
                     _ => {}
                 }
@@ -63,12 +62,21 @@ impl MQTTTask {
             let ip = ip.to_owned();
             log::info!("Starting MQTT Status Thread");
             let _update_t = thread::spawn(move || loop {
-                let _ = StaticMqttManager::publish(&ip_topic, ip.as_bytes(), false);
+                let _ =
+                    StaticMqttManager::publish(&ip_topic, ip.as_bytes(), QoS::AtMostOnce, false);
                 thread::sleep(Duration::from_secs(30));
             });
 
             // Subscribe to ring topic
-            StaticMqttManager::subscribe(&self.0.ring_topic)?;
+            StaticMqttManager::subscribe_with_handler(
+                &self.0.ring_topic,
+                QoS::AtMostOnce,
+                move |topic, data, _| {
+                    let data = String::from_utf8_lossy(data);
+                    log::info!("mqtt_rx: {topic} : {data}");
+                    f_ring(&data);
+                },
+            )?;
         }
 
         Ok(())
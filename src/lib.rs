@@ -5,6 +5,7 @@ pub mod button;
 pub mod mqtt;
 pub mod nvs;
 pub mod ota;
+pub mod template;
 pub mod web;
 pub mod wifi;
 pub mod ws2812;
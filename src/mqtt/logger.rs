@@ -0,0 +1,179 @@
+use esp_idf_svc::log::EspLogger;
+use log::{LevelFilter, Log, Metadata, Record};
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::{QoS, StaticMqttManager};
+
+const LOG_BUFFER_SIZE: usize = 32;
+const LOG_MAX_LEN: usize = 256;
+const FORWARD_INTERVAL: Duration = Duration::from_millis(250);
+
+// Never forward MQTT client logs (publishing would generate more log messages)
+const EXCLUDED_TARGETS: [&str; 2] = ["esp_idf_svc::mqtt", "mqtt_client"];
+
+// Library MQTT module is matched on source path as the module path
+// (doorbell::mqtt) can clash with application modules
+fn is_mqtt_module(record: &Record) -> bool {
+    let dir = file!().trim_end_matches("logger.rs");
+    record.file().is_some_and(|f| f.starts_with(dir))
+}
+
+static MQTT_LOGGER: MqttLogger = MqttLogger {
+    esp_logger: EspLogger::new(),
+    enabled: AtomicBool::new(false),
+    level: AtomicUsize::new(LevelFilter::Info as usize),
+    buffer: Mutex::new(VecDeque::new()),
+    dropped: AtomicU32::new(0),
+};
+
+// log::Log backend which writes to EspLogger and forwards records to MQTT.
+// Records are added to a ring buffer (never blocking the caller) which is
+// drained by a rate limited forwarding thread.
+pub struct MqttLogger {
+    esp_logger: EspLogger,
+    enabled: AtomicBool,
+    level: AtomicUsize,
+    buffer: Mutex<VecDeque<String>>,
+    dropped: AtomicU32,
+}
+
+impl MqttLogger {
+    // Use in place of EspLogger::initialize_default()
+    pub fn initialize() -> anyhow::Result<()> {
+        log::set_logger(&MQTT_LOGGER).map_err(|e| anyhow::anyhow!("Logger Error: {e}"))?;
+        log::set_max_level(MQTT_LOGGER.esp_logger.get_max_level());
+        Ok(())
+    }
+
+    // Start forwarding thread - rate is max messages/sec
+    pub fn start(topic: &str, level: LevelFilter, rate: u32) -> anyhow::Result<()> {
+        Self::set_level(level);
+        let topic = topic.to_owned();
+        let rate = rate.max(1) as f32;
+        std::thread::Builder::new()
+            .stack_size(4096)
+            .spawn(move || {
+                let mut tokens = rate;
+                let mut last = Instant::now();
+                loop {
+                    std::thread::sleep(FORWARD_INTERVAL);
+                    tokens = (tokens + last.elapsed().as_secs_f32() * rate).min(rate);
+                    last = Instant::now();
+                    let connected = StaticMqttManager::status()
+                        .map(|s| s.connected)
+                        .unwrap_or(false);
+                    if !Self::is_enabled() || !connected {
+                        continue;
+                    }
+                    while tokens >= 1.0 {
+                        let Some(msg) = MQTT_LOGGER
+                            .buffer
+                            .try_lock()
+                            .ok()
+                            .and_then(|mut b| b.pop_front())
+                        else {
+                            break;
+                        };
+                        let _ = StaticMqttManager::publish(
+                            &topic,
+                            msg.as_bytes(),
+                            QoS::AtMostOnce,
+                            false,
+                        );
+                        tokens -= 1.0;
+                    }
+                }
+            })?;
+        Ok(())
+    }
+
+    pub fn set_enabled(enabled: bool) {
+        MQTT_LOGGER.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            if let Ok(mut buffer) = MQTT_LOGGER.buffer.lock() {
+                buffer.clear();
+            }
+        }
+    }
+
+    pub fn is_enabled() -> bool {
+        MQTT_LOGGER.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_level(level: LevelFilter) {
+        MQTT_LOGGER.level.store(level as usize, Ordering::Relaxed);
+    }
+
+    pub fn level() -> LevelFilter {
+        match MQTT_LOGGER.level.load(Ordering::Relaxed) {
+            0 => LevelFilter::Off,
+            1 => LevelFilter::Error,
+            2 => LevelFilter::Warn,
+            3 => LevelFilter::Info,
+            4 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    }
+
+    // Messages dropped (buffer full or busy)
+    pub fn dropped() -> u32 {
+        MQTT_LOGGER.dropped.load(Ordering::Relaxed)
+    }
+
+    fn forward(&self, record: &Record) {
+        let mut msg = format!("{} [{}] {}", record.level(), record.target(), record.args());
+        if msg.len() > LOG_MAX_LEN {
+            let mut end = LOG_MAX_LEN;
+            while !msg.is_char_boundary(end) {
+                end -= 1;
+            }
+            msg.truncate(end);
+        }
+        // Never block caller - drop message if buffer is in use
+        match self.buffer.try_lock() {
+            Ok(mut buffer) => {
+                if buffer.len() >= LOG_BUFFER_SIZE {
+                    buffer.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                buffer.push_back(msg);
+            }
+            Err(_) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+// Parse log level from config (off/error/warn/info/debug/trace)
+pub fn parse_level(level: &str) -> anyhow::Result<LevelFilter> {
+    LevelFilter::from_str(level.trim()).map_err(|_| anyhow::anyhow!("Invalid log level: {level}"))
+}
+
+impl Log for MqttLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.esp_logger.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.esp_logger.log(record);
+        if self.enabled.load(Ordering::Relaxed)
+            && record.level() <= Self::level()
+            && !EXCLUDED_TARGETS
+                .iter()
+                .any(|t| record.target().starts_with(t))
+            && !is_mqtt_module(record)
+        {
+            self.forward(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.esp_logger.flush();
+    }
+}
@@ -1,37 +1,190 @@
 use esp_idf_svc::mqtt::client::{
-    Details, EspMqttClient, EventPayload, MqttClientConfiguration, QoS,
+    Details, EspMqttClient, EventPayload, LwtConfiguration, MqttClientConfiguration,
+    MqttProtocolVersion,
 };
+use esp_idf_svc::tls::X509;
+
+use serde::Serialize;
 
 use core::time::Duration;
-use std::sync::{mpsc, Mutex};
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc, Mutex};
+
+mod logger;
+mod mqtt5;
+
+pub use esp_idf_svc::mqtt::client::QoS;
+pub use logger::{parse_level, MqttLogger};
 
 const MQTT_RETRY_COUNT: u32 = 5;
 
+// Default number of publishes buffered while disconnected
+pub const MQTT_QUEUE_SIZE: usize = 32;
+
+// Availability payloads (LWT sets offline if connection lost)
+pub const AVAILABILITY_ONLINE: &str = "online";
+pub const AVAILABILITY_OFFLINE: &str = "offline";
+
 pub enum MqttMessage {
     Message(String, Vec<u8>),
     Reconnected,
 }
 
+// MQTT v5 message properties (ignored for v3.1.1)
+#[derive(Clone, Debug, Default)]
+pub struct MessageProperties {
+    pub message_expiry: Option<u32>,
+    pub response_topic: Option<String>,
+    pub correlation_data: Option<Vec<u8>>,
+}
+
+// Per-topic message handler (called with topic, payload and properties)
+pub type MqttHandler = Arc<dyn Fn(&str, &[u8], &MessageProperties) + Send + Sync + 'static>;
+
+// Active subscription - restored automatically after reconnect
+#[derive(Clone)]
+struct Subscription {
+    topic: String,
+    qos: QoS,
+    handler: Option<MqttHandler>,
+}
+
+type Subscriptions = Arc<Mutex<Vec<Subscription>>>;
+
+// Events passed from connection thread to dispatch thread
+enum ConnectionEvent {
+    Message(String, Vec<u8>, MessageProperties),
+    Connected(bool), // true if reconnect
+    Disconnected,
+}
+
+struct QueuedMessage {
+    topic: String,
+    message: Vec<u8>,
+    qos: QoS,
+    retain: bool,
+    properties: MessageProperties,
+}
+
+// Publishes made while disconnected - oldest messages dropped when full
+struct PublishQueue {
+    connected: bool,
+    messages: VecDeque<QueuedMessage>,
+    size: usize,
+    dropped: u32,
+}
+
+impl PublishQueue {
+    fn new(size: usize) -> Self {
+        Self {
+            connected: false,
+            messages: VecDeque::new(),
+            size,
+            dropped: 0,
+        }
+    }
+
+    fn push(&mut self, msg: QueuedMessage) {
+        while self.messages.len() >= self.size.max(1) {
+            self.messages.pop_front();
+            self.dropped += 1;
+        }
+        self.messages.push_back(msg);
+    }
+}
+
+type SharedQueue = Arc<Mutex<PublishQueue>>;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MqttStatus {
+    pub connected: bool,
+    pub queued: usize,
+    pub queue_size: usize,
+    pub dropped: u32,
+}
+
+// Convert QoS level (0-2) from config
+pub fn qos_from_level(level: u8) -> anyhow::Result<QoS> {
+    match level {
+        0 => Ok(QoS::AtMostOnce),
+        1 => Ok(QoS::AtLeastOnce),
+        2 => Ok(QoS::ExactlyOnce),
+        _ => Err(anyhow::anyhow!("Invalid QoS level: {level}")),
+    }
+}
+
+// Match topic against subscription filter (supports '+' and '#' wildcards)
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut filter = filter.split('/');
+    let mut topic = topic.split('/');
+    loop {
+        match (filter.next(), topic.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => {}
+            (Some(f), Some(t)) if f == t => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+// Connection options - certificates are PEM strings
+#[derive(Clone, Default)]
+pub struct MqttOptions {
+    pub client_id: Option<String>,
+    pub availability_topic: Option<String>,
+    pub availability_payloads: Option<(String, String)>, // (online, offline)
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub crt_bundle: bool,
+    pub ca_cert: Option<String>,
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+    pub queue_size: Option<usize>,
+    pub mqtt5: bool,
+    pub user_properties: Vec<(String, String)>, // v5 only
+}
+
+// MQTT client requires 'static NUL terminated certificates - these are leaked
+// (client is only created once)
+fn leak_pem(pem: &Option<String>) -> Option<X509<'static>> {
+    pem.as_ref().map(|pem| {
+        let pem: &'static [u8] = Box::leak(format!("{pem}\0").into_bytes().into_boxed_slice());
+        X509::pem_until_nul(pem)
+    })
+}
+
 static MQTT_MANAGER: Mutex<Option<MqttManager>> = Mutex::new(None);
 
 pub struct StaticMqttManager {}
 
 impl StaticMqttManager {
-    pub fn init(url: &str, client_id: Option<&str>) -> anyhow::Result<mpsc::Receiver<MqttMessage>> {
+    pub fn init(url: &str, options: &MqttOptions) -> anyhow::Result<mpsc::Receiver<MqttMessage>> {
         let (tx, rx) = mpsc::channel::<MqttMessage>();
-        let mqtt_manager = MqttManager::new(url, client_id, tx)?;
+        let mqtt_manager = MqttManager::new(url, options, tx)?;
         MQTT_MANAGER
             .replace(Some(mqtt_manager))
             .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?;
         Ok(rx)
     }
-    pub fn subscribe(topic: &str) -> anyhow::Result<()> {
+    pub fn subscribe(topic: &str, qos: QoS) -> anyhow::Result<()> {
+        MQTT_MANAGER
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("MQTT_MANAGER not initialised"))?
+            .subscribe(topic, qos)
+    }
+    pub fn subscribe_with_handler<F>(topic: &str, qos: QoS, handler: F) -> anyhow::Result<()>
+    where
+        F: Fn(&str, &[u8], &MessageProperties) + Send + Sync + 'static,
+    {
         MQTT_MANAGER
             .lock()
             .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?
             .as_mut()
             .ok_or_else(|| anyhow::anyhow!("MQTT_MANAGER not initialised"))?
-            .subscribe(topic)
+            .subscribe_with_handler(topic, qos, handler)
     }
     pub fn unsubscribe(topic: &str) -> anyhow::Result<()> {
         MQTT_MANAGER
@@ -41,65 +194,229 @@ impl StaticMqttManager {
             .ok_or_else(|| anyhow::anyhow!("MQTT_MANAGER not initialised"))?
             .unsubscribe(topic)
     }
-    pub fn publish(topic: &str, message: &[u8], retain: bool) -> anyhow::Result<u32> {
+    pub fn publish(topic: &str, message: &[u8], qos: QoS, retain: bool) -> anyhow::Result<u32> {
         MQTT_MANAGER
             .lock()
             .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?
             .as_mut()
             .ok_or_else(|| anyhow::anyhow!("MQTT_MANAGER not initialised"))?
-            .publish(topic, message, retain)
+            .publish(topic, message, qos, retain)
+    }
+    pub fn publish_with_properties(
+        topic: &str,
+        message: &[u8],
+        qos: QoS,
+        retain: bool,
+        properties: &MessageProperties,
+    ) -> anyhow::Result<u32> {
+        MQTT_MANAGER
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("MQTT_MANAGER not initialised"))?
+            .publish_with_properties(topic, message, qos, retain, properties)
+    }
+    pub fn status() -> anyhow::Result<MqttStatus> {
+        MQTT_MANAGER
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("MQTT_MANAGER not initialised"))?
+            .status()
+    }
+    pub fn publish_online() -> anyhow::Result<()> {
+        MQTT_MANAGER
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("MQTT_MANAGER not initialised"))?
+            .publish_online()
+    }
+}
+
+// Shared client - all publishes go through Client so v5 properties are set
+struct Client {
+    client: Mutex<EspMqttClient<'static>>,
+    user_properties: Option<mqtt5::UserProperties>, // Some if v5
+    availability: Option<(String, String)>,         // (topic, online payload)
+}
+
+impl Client {
+    fn publish(
+        &self,
+        topic: &str,
+        message: &[u8],
+        qos: QoS,
+        retain: bool,
+        properties: &MessageProperties,
+    ) -> anyhow::Result<u32> {
+        let mut client = self
+            .client
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?;
+        match &self.user_properties {
+            Some(user_properties) => mqtt5::publish(
+                &mut client,
+                user_properties,
+                topic,
+                qos,
+                retain,
+                message,
+                properties,
+            ),
+            None => client
+                .enqueue(topic, qos, retain, message)
+                .map_err(|e| anyhow::anyhow!("MQTT Error: {e}")),
+        }
+    }
+
+    fn subscribe(&self, topic: &str, qos: QoS) -> anyhow::Result<u32> {
+        self.client
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?
+            .subscribe(topic, qos)
+            .map_err(|e| anyhow::anyhow!("MQTT Error: {e}"))
+    }
+
+    fn unsubscribe(&self, topic: &str) -> anyhow::Result<u32> {
+        self.client
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?
+            .unsubscribe(topic)
+            .map_err(|e| anyhow::anyhow!("MQTT Error: {e}"))
+    }
+
+    fn publish_availability(&self) -> anyhow::Result<()> {
+        if let Some((topic, online)) = &self.availability {
+            self.publish(
+                topic,
+                online.as_bytes(),
+                QoS::AtLeastOnce,
+                true,
+                &MessageProperties::default(),
+            )?;
+            log::info!("Published availability: {topic}");
+        }
+        Ok(())
     }
 }
 
 pub struct MqttManager {
-    client: EspMqttClient<'static>,
+    client: Arc<Client>,
+    subscriptions: Subscriptions,
+    queue: SharedQueue,
     _conn_handle: std::thread::JoinHandle<()>,
+    _dispatch_handle: std::thread::JoinHandle<()>,
 }
 
 impl MqttManager {
     pub fn new(
         url: &str,
-        client_id: Option<&str>,
+        options: &MqttOptions,
         tx: mpsc::Sender<MqttMessage>,
     ) -> anyhow::Result<Self> {
         log::info!("Creating MqttClient: {url}");
+        let (online, offline) = options.availability_payloads.clone().unwrap_or_else(|| {
+            (
+                AVAILABILITY_ONLINE.to_string(),
+                AVAILABILITY_OFFLINE.to_string(),
+            )
+        });
         let (client, mut connection) = EspMqttClient::new(
             url,
             &MqttClientConfiguration {
-                client_id,
+                client_id: options.client_id.as_deref(),
                 keep_alive_interval: Some(Duration::from_secs(30)),
+                lwt: options
+                    .availability_topic
+                    .as_deref()
+                    .map(|topic| LwtConfiguration {
+                        topic,
+                        payload: offline.as_bytes(),
+                        qos: QoS::AtLeastOnce,
+                        retain: true,
+                    }),
+                username: options.username.as_deref(),
+                password: options.password.as_deref(),
+                crt_bundle_attach: if options.crt_bundle {
+                    Some(esp_idf_svc::sys::esp_crt_bundle_attach)
+                } else {
+                    None
+                },
+                server_certificate: leak_pem(&options.ca_cert),
+                client_certificate: leak_pem(&options.client_cert),
+                private_key: leak_pem(&options.client_key),
+                protocol_version: Some(if options.mqtt5 {
+                    MqttProtocolVersion::V5
+                } else {
+                    MqttProtocolVersion::V3_1_1
+                }),
                 ..Default::default()
             },
         )?;
+        let subscriptions: Subscriptions = Arc::new(Mutex::new(Vec::new()));
+        let queue: SharedQueue = Arc::new(Mutex::new(PublishQueue::new(
+            options.queue_size.unwrap_or(MQTT_QUEUE_SIZE),
+        )));
+
+        // The client cannot be used from the connection thread (the MQTT task
+        // blocks until each event is consumed) so events are forwarded to a
+        // separate dispatch thread which resubscribes, flushes queued
+        // publishes and runs handlers
+        let (event_tx, event_rx) = mpsc::channel::<ConnectionEvent>();
+
+        let mqtt5 = options.mqtt5;
+        if mqtt5 {
+            mqtt5::register_data_handler(&client, event_tx.clone())?;
+        }
+        let client = Arc::new(Client {
+            client: Mutex::new(client),
+            user_properties: if mqtt5 {
+                Some(mqtt5::user_properties(&options.user_properties)?)
+            } else {
+                None
+            },
+            availability: options
+                .availability_topic
+                .clone()
+                .map(|topic| (topic, online)),
+        });
 
         // Handle events in thread
         let _conn_handle = std::thread::Builder::new()
             .stack_size(8192)
             .spawn(move || {
-                // TODO Remove has_disconnected test
                 let mut has_disconnected = false;
                 log::info!("MQTT Listening for messages");
                 while let Ok(event) = connection.next() {
                     log::info!("[Queue] Event: {}", event.payload());
                     match event.payload() {
+                        // v5 messages are handled by mqtt5 data handler
                         EventPayload::Received {
                             topic: Some(t),
                             details: Details::Complete,
                             data,
                             ..
-                        } => tx
-                            .send(MqttMessage::Message(t.to_owned(), data.to_vec()))
+                        } if !mqtt5 => event_tx
+                            .send(ConnectionEvent::Message(
+                                t.to_owned(),
+                                data.to_vec(),
+                                MessageProperties::default(),
+                            ))
                             .unwrap_or(()),
                         EventPayload::Connected(_) => {
                             if has_disconnected {
                                 log::info!("MQTT Reconnected");
-                                has_disconnected = false;
-                                tx.send(MqttMessage::Reconnected).unwrap_or(())
                             }
+                            event_tx
+                                .send(ConnectionEvent::Connected(has_disconnected))
+                                .unwrap_or(());
+                            has_disconnected = false;
                         }
                         EventPayload::Disconnected => {
                             log::info!("MQTT disconnected");
                             has_disconnected = true;
+                            event_tx.send(ConnectionEvent::Disconnected).unwrap_or(())
                         }
                         _ => {}
                     }
@@ -107,18 +424,116 @@ impl MqttManager {
                 log::info!("Connection closed");
             })?;
 
+        // Dispatch events - restore subscriptions on connect, flush queued
+        // publishes and pass messages to matching handlers (unhandled messages
+        // are forwarded to tx)
+        let _dispatch_handle = {
+            let client = client.clone();
+            let subscriptions = subscriptions.clone();
+            let queue = queue.clone();
+            std::thread::Builder::new()
+                .stack_size(8192)
+                .spawn(move || {
+                    while let Ok(event) = event_rx.recv() {
+                        match event {
+                            ConnectionEvent::Connected(reconnect) => {
+                                if let Err(e) = client.publish_availability() {
+                                    log::error!("Failed to publish availability: {e}");
+                                }
+                                flush(&client, &queue);
+                                // After flush marks connected so subscriptions
+                                // registered while disconnected are not missed
+                                restore(&client, &subscriptions);
+                                if reconnect {
+                                    tx.send(MqttMessage::Reconnected).unwrap_or(())
+                                }
+                            }
+                            ConnectionEvent::Disconnected => {
+                                if let Ok(mut queue) = queue.lock() {
+                                    queue.connected = false;
+                                }
+                            }
+                            ConnectionEvent::Message(topic, data, properties) => {
+                                let handlers = subscriptions
+                                    .lock()
+                                    .map(|s| {
+                                        s.iter()
+                                            .filter(|s| topic_matches(&s.topic, &topic))
+                                            .filter_map(|s| s.handler.clone())
+                                            .collect::<Vec<_>>()
+                                    })
+                                    .unwrap_or_default();
+                                if handlers.is_empty() {
+                                    tx.send(MqttMessage::Message(topic, data)).unwrap_or(())
+                                } else {
+                                    handlers.iter().for_each(|h| h(&topic, &data, &properties));
+                                }
+                            }
+                        }
+                    }
+                    log::info!("Dispatch thread closed");
+                })?
+        };
+
         // Allow thread to start processing events before returning
         std::thread::sleep(Duration::from_millis(500));
 
         Ok(Self {
             client,
+            subscriptions,
+            queue,
             _conn_handle,
+            _dispatch_handle,
         })
     }
 
-    pub fn subscribe(&mut self, topic: &str) -> anyhow::Result<()> {
+    pub fn subscribe(&mut self, topic: &str, qos: QoS) -> anyhow::Result<()> {
+        self.register(Subscription {
+            topic: topic.to_owned(),
+            qos,
+            handler: None,
+        })
+    }
+
+    pub fn subscribe_with_handler<F>(
+        &mut self,
+        topic: &str,
+        qos: QoS,
+        handler: F,
+    ) -> anyhow::Result<()>
+    where
+        F: Fn(&str, &[u8], &MessageProperties) + Send + Sync + 'static,
+    {
+        self.register(Subscription {
+            topic: topic.to_owned(),
+            qos,
+            handler: Some(Arc::new(handler)),
+        })
+    }
+
+    // Add to registry (replacing any existing subscription) and subscribe if
+    // connected (otherwise subscribed when connection is established)
+    fn register(&mut self, subscription: Subscription) -> anyhow::Result<()> {
+        let (topic, qos) = (subscription.topic.clone(), subscription.qos);
+        {
+            let mut subscriptions = self
+                .subscriptions
+                .lock()
+                .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?;
+            subscriptions.retain(|s| s.topic != topic);
+            subscriptions.push(subscription);
+        }
+        let connected = self
+            .queue
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?
+            .connected;
+        if !connected {
+            log::info!("Subscription pending connection: {topic}");
+            return Ok(());
+        }
         for _ in 0..=MQTT_RETRY_COUNT {
-            match self.client.subscribe(topic, QoS::AtMostOnce) {
+            match self.client.subscribe(&topic, qos) {
                 Ok(_) => {
                     log::info!("Subscribed: {topic}");
                     return Ok(());
@@ -133,18 +548,126 @@ impl MqttManager {
     }
 
     pub fn unsubscribe(&mut self, topic: &str) -> anyhow::Result<()> {
-        self.client
-            .unsubscribe(topic)
-            .map(|_| {
-                log::info!("Unsubscribed: {topic}");
-            })
-            .map_err(|e| anyhow::anyhow!("MQTT Error: {e}"))
+        self.subscriptions
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?
+            .retain(|s| s.topic != topic);
+        self.client.unsubscribe(topic).map(|_| {
+            log::info!("Unsubscribed: {topic}");
+        })
     }
 
-    pub fn publish(&mut self, topic: &str, message: &[u8], retain: bool) -> anyhow::Result<u32> {
-        self.client
-            .enqueue(topic, QoS::AtMostOnce, retain, message)
-            .map_err(|e| anyhow::anyhow!("MQTT Error: {e}"))
+    // Active subscriptions (topic, qos)
+    pub fn subscriptions(&self) -> Vec<(String, QoS)> {
+        self.subscriptions
+            .lock()
+            .map(|s| s.iter().map(|s| (s.topic.clone(), s.qos)).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn publish(
+        &mut self,
+        topic: &str,
+        message: &[u8],
+        qos: QoS,
+        retain: bool,
+    ) -> anyhow::Result<u32> {
+        self.publish_with_properties(topic, message, qos, retain, &MessageProperties::default())
+    }
+
+    // Publish message (queued if disconnected - returns 0 if queued)
+    pub fn publish_with_properties(
+        &mut self,
+        topic: &str,
+        message: &[u8],
+        qos: QoS,
+        retain: bool,
+        properties: &MessageProperties,
+    ) -> anyhow::Result<u32> {
+        let mut queue = self
+            .queue
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?;
+        if queue.connected {
+            // Release queue before publishing (flush holds queue lock)
+            drop(queue);
+            self.client.publish(topic, message, qos, retain, properties)
+        } else {
+            queue.push(QueuedMessage {
+                topic: topic.to_owned(),
+                message: message.to_vec(),
+                qos,
+                retain,
+                properties: properties.clone(),
+            });
+            Ok(0)
+        }
+    }
+
+    pub fn status(&self) -> anyhow::Result<MqttStatus> {
+        let queue = self
+            .queue
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?;
+        Ok(MqttStatus {
+            connected: queue.connected,
+            queued: queue.messages.len(),
+            queue_size: queue.size,
+            dropped: queue.dropped,
+        })
+    }
+
+    // Publish retained online status (call after connect - republished
+    // automatically after reconnect)
+    pub fn publish_online(&mut self) -> anyhow::Result<()> {
+        // Use queue so online is published after any earlier (queued) messages
+        if let Some((topic, online)) = self.client.availability.clone() {
+            self.publish(&topic, online.as_bytes(), QoS::AtLeastOnce, true)?;
+        }
+        Ok(())
+    }
+}
+
+// Publish queued messages in order and mark connected (queue lock is held so
+// new publishes wait until flush is complete)
+fn flush(client: &Client, queue: &Mutex<PublishQueue>) {
+    let Ok(mut queue) = queue.lock() else {
+        return;
+    };
+    if !queue.messages.is_empty() {
+        log::info!("Flushing {} queued messages", queue.messages.len());
+    }
+    while let Some(msg) = queue.messages.pop_front() {
+        let result = client.publish(
+            &msg.topic,
+            &msg.message,
+            msg.qos,
+            msg.retain,
+            &msg.properties,
+        );
+        if let Err(e) = result {
+            log::error!("Failed to publish queued message: {} [{e}]", msg.topic);
+            queue.dropped += 1;
+        }
+    }
+    queue.connected = true;
+}
+
+// Subscribe to registered topics after connect/reconnect
+fn restore(client: &Client, subscriptions: &Mutex<Vec<Subscription>>) {
+    let topics = subscriptions
+        .lock()
+        .map(|s| {
+            s.iter()
+                .map(|s| (s.topic.clone(), s.qos))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    for (topic, qos) in topics {
+        match client.subscribe(&topic, qos) {
+            Ok(_) => log::info!("Subscribed: {topic}"),
+            Err(e) => log::error!("Failed to subscribe: {topic} [{e}]"),
+        }
     }
 }
 
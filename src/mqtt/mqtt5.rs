@@ -0,0 +1,146 @@
+// MQTT v5 properties - esp-idf-svc does not wrap the mqtt5 property API so
+// this uses esp-idf-sys directly (requires CONFIG_MQTT_PROTOCOL_5)
+use esp_idf_svc::handle::RawHandle;
+use esp_idf_svc::mqtt::client::EspMqttClient;
+use esp_idf_svc::sys::{
+    esp, esp_event_base_t, esp_mqtt5_client_delete_user_property,
+    esp_mqtt5_client_set_publish_property, esp_mqtt5_client_set_user_property,
+    esp_mqtt5_publish_property_config_t, esp_mqtt5_user_property_item_t,
+    esp_mqtt_client_register_event, esp_mqtt_event_id_t_MQTT_EVENT_DATA, esp_mqtt_event_t,
+};
+
+use std::ffi::{c_char, c_void, CString};
+use std::sync::mpsc;
+
+use super::{ConnectionEvent, MessageProperties, QoS};
+
+// User properties are attached to every publish
+pub(super) type UserProperties = Vec<(CString, CString)>;
+
+pub(super) fn user_properties(properties: &[(String, String)]) -> anyhow::Result<UserProperties> {
+    properties
+        .iter()
+        .map(|(k, v)| Ok((CString::new(k.as_str())?, CString::new(v.as_str())?)))
+        .collect()
+}
+
+// Publish properties apply to the next publish so must be set (with the client
+// locked) immediately before each publish
+pub(super) fn publish(
+    client: &mut EspMqttClient<'static>,
+    user_properties: &UserProperties,
+    topic: &str,
+    qos: QoS,
+    retain: bool,
+    message: &[u8],
+    properties: &MessageProperties,
+) -> anyhow::Result<u32> {
+    let response_topic = properties
+        .response_topic
+        .as_deref()
+        .map(CString::new)
+        .transpose()?;
+    let mut items = user_properties
+        .iter()
+        .map(|(k, v)| esp_mqtt5_user_property_item_t {
+            key: k.as_ptr(),
+            value: v.as_ptr(),
+        })
+        .collect::<Vec<_>>();
+    let mut config = esp_mqtt5_publish_property_config_t {
+        message_expiry_interval: properties.message_expiry.unwrap_or(0),
+        response_topic: response_topic
+            .as_ref()
+            .map_or(std::ptr::null(), |t| t.as_ptr()),
+        correlation_data: properties
+            .correlation_data
+            .as_ref()
+            .map_or(std::ptr::null(), |d| d.as_ptr() as *const c_char),
+        correlation_data_len: properties
+            .correlation_data
+            .as_ref()
+            .map_or(0, |d| d.len() as u16),
+        ..Default::default()
+    };
+    if !items.is_empty() {
+        esp!(unsafe {
+            esp_mqtt5_client_set_user_property(
+                &mut config.user_property,
+                items.as_mut_ptr(),
+                items.len() as u8,
+            )
+        })?;
+    }
+    let result = esp!(unsafe { esp_mqtt5_client_set_publish_property(client.handle(), &config) })
+        .map_err(anyhow::Error::from)
+        .and_then(|_| {
+            client
+                .enqueue(topic, qos, retain, message)
+                .map_err(|e| anyhow::anyhow!("MQTT Error: {e}"))
+        });
+    // Property list is copied by client
+    if !config.user_property.is_null() {
+        unsafe { esp_mqtt5_client_delete_user_property(config.user_property) };
+    }
+    result
+}
+
+// EventPayload::Received does not include properties so (for v5) received
+// messages are read directly from the MQTT_EVENT_DATA event
+pub(super) fn register_data_handler(
+    client: &EspMqttClient<'static>,
+    tx: mpsc::Sender<ConnectionEvent>,
+) -> anyhow::Result<()> {
+    // Client is only created once so sender is leaked
+    let tx: &'static mpsc::Sender<ConnectionEvent> = Box::leak(Box::new(tx));
+    esp!(unsafe {
+        esp_mqtt_client_register_event(
+            client.handle(),
+            esp_mqtt_event_id_t_MQTT_EVENT_DATA,
+            Some(data_handler),
+            tx as *const _ as *mut c_void,
+        )
+    })?;
+    Ok(())
+}
+
+unsafe fn to_vec(data: *const c_char, len: usize) -> Vec<u8> {
+    if data.is_null() || len == 0 {
+        Vec::new()
+    } else {
+        std::slice::from_raw_parts(data as *const u8, len).to_vec()
+    }
+}
+
+// Runs in MQTT task - must not block
+unsafe extern "C" fn data_handler(
+    arg: *mut c_void,
+    _base: esp_event_base_t,
+    _id: i32,
+    data: *mut c_void,
+) {
+    let tx = &*(arg as *const mpsc::Sender<ConnectionEvent>);
+    let event = &*(data as *const esp_mqtt_event_t);
+    // Only complete (unfragmented) messages are handled
+    if event.topic.is_null() || event.data_len != event.total_data_len {
+        return;
+    }
+    let topic = String::from_utf8_lossy(&to_vec(event.topic, event.topic_len as usize)).to_string();
+    let message = to_vec(event.data, event.data_len as usize);
+    let properties = if event.property.is_null() {
+        MessageProperties::default()
+    } else {
+        let p = &*event.property;
+        MessageProperties {
+            message_expiry: None,
+            response_topic: (!p.response_topic.is_null() && p.response_topic_len > 0).then(|| {
+                String::from_utf8_lossy(&to_vec(p.response_topic, p.response_topic_len as usize))
+                    .to_string()
+            }),
+            correlation_data: (!p.correlation_data.is_null() && p.correlation_data_len > 0)
+                .then(|| to_vec(p.correlation_data, p.correlation_data_len as usize)),
+        }
+    };
+    tx.send(ConnectionEvent::Message(topic, message, properties))
+        .unwrap_or(());
+}
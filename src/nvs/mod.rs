@@ -11,7 +11,7 @@ use std::sync::Mutex;
 mod web;
 
 pub static NV_STORE: Mutex<Option<EspNvs<NvsDefault>>> = Mutex::new(None);
-const NV_STORE_MAX: usize = 2048; // Maximum size for serialised data
+pub const NV_STORE_MAX: usize = 4096; // Maximum size for serialised data (fits PEM certs)
 
 pub struct NVStore {}
 
@@ -45,7 +45,8 @@ impl NVStore {
         let nvs = nvs
             .as_ref()
             .ok_or(anyhow::anyhow!("NV_STORE not initialized"))?;
-        let mut buf = [0_u8; NV_STORE_MAX];
+        // Heap buffer - may be called from threads with small stacks
+        let mut buf = vec![0_u8; NV_STORE_MAX];
         if let Some(data) = nvs.get_raw(key, &mut buf)? {
             Ok(Some(serde_json::from_slice(data)?))
         } else {
@@ -58,7 +59,8 @@ impl NVStore {
         let nvs = nvs
             .as_ref()
             .ok_or(anyhow::anyhow!("NV_STORE not initialized"))?;
-        let mut buf = [0_u8; NV_STORE_MAX];
+        // Heap buffer - may be called from threads with small stacks
+        let mut buf = vec![0_u8; NV_STORE_MAX];
         if let Some(data) = nvs.get_raw(key, &mut buf)? {
             Ok(Some(data.to_vec()))
         } else {
@@ -75,6 +77,9 @@ impl NVStore {
             .as_mut()
             .ok_or(anyhow::anyhow!("NV_STORE not initialized"))?;
         let data = serde_json::to_vec(value)?;
+        if data.len() > NV_STORE_MAX {
+            anyhow::bail!("Value too large for key {key}: {} bytes", data.len());
+        }
         nvs.set_raw(key, data.as_slice())
             .map_err(|e| anyhow::anyhow!("Error updating key {key}: [{}]", e))?;
         Ok(())
@@ -88,6 +93,9 @@ impl NVStore {
 
         // Check body is valid JSON
         serde_json::from_slice::<serde_json::Value>(value)?;
+        if value.len() > NV_STORE_MAX {
+            anyhow::bail!("Value too large for key {key}: {} bytes", value.len());
+        }
 
         nvs.set_raw(key, value)
             .map_err(|e| anyhow::anyhow!("Error updating key {key}: [{}]", e))?;
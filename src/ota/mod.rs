@@ -1,4 +1,8 @@
 use askama::Template;
+use embedded_svc::http::client::Client as HttpClient;
+use esp_idf_svc::http::client::{
+    Configuration as HttpConfiguration, EspHttpConnection as HttpConnection,
+};
 use esp_idf_svc::http::server::{EspHttpConnection, Request};
 use esp_idf_svc::http::Method;
 
@@ -126,3 +130,40 @@ pub fn rollback_handler(request: Request<&mut EspHttpConnection>) -> anyhow::Res
 
     Ok::<(), anyhow::Error>(())
 }
+
+// Download firmware image from url and set as boot partition (restarts on success)
+pub fn ota_from_url(url: &str) -> anyhow::Result<()> {
+    let http_config = HttpConfiguration {
+        crt_bundle_attach: Some(esp_idf_svc::sys::esp_crt_bundle_attach),
+        ..Default::default()
+    };
+    let mut client = HttpClient::wrap(HttpConnection::new(&http_config)?);
+
+    log::info!("Starting OTA Update: {url}");
+    let mut response = client.get(url)?.submit()?;
+    if response.status() != 200 {
+        anyhow::bail!("OTA HTTP Error: {}", response.status());
+    }
+
+    let mut buf = [0_u8; 1024];
+    let mut total = 0_usize;
+    let mut ota = esp_ota::OtaUpdate::begin()?;
+    loop {
+        let len = embedded_svc::io::Read::read(&mut response, &mut buf)?;
+        if len == 0 {
+            break;
+        }
+        total += len;
+        ota.write(&buf[0..len])?;
+    }
+    log::info!("OTA Image: {total} bytes");
+
+    let mut completed_ota = ota.finalize()?;
+    completed_ota.set_as_boot_partition()?;
+    let _ = std::thread::spawn(move || {
+        log::info!("OTA Restarting");
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        esp_idf_hal::reset::restart();
+    });
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use esp_idf_svc::http::server::{EspHttpConnection, Request};
+
+// Simple message templates - "{name}" is replaced with the context value
+// and "{{" / "}}" are used for literal braces (eg. JSON payloads)
+
+pub const PLACEHOLDERS: [&str; 7] = [
+    "device",
+    "time",
+    "duration",
+    "count_today",
+    "stddev",
+    "channel",
+    "ip",
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct TemplateContext(Vec<(&'static str, String)>);
+
+impl TemplateContext {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn with(mut self, key: &'static str, value: impl ToString) -> Self {
+        self.0.retain(|(k, _)| *k != key);
+        self.0.push((key, value.to_string()));
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    // Example values used for template preview
+    pub fn sample() -> Self {
+        Self::new()
+            .with("device", "Doorbell-A1B2")
+            .with("time", "2025-01-01 12:00:00 UTC")
+            .with("duration", "2.5")
+            .with("count_today", 3)
+            .with("stddev", "0.0421")
+            .with("channel", "doorbell")
+            .with("ip", "192.168.1.100")
+    }
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+fn parse(template: &str) -> anyhow::Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        if i > 0 {
+            tokens.push(Token::Text(&rest[..i]));
+        }
+        rest = &rest[i..];
+        if rest.starts_with("{{") {
+            tokens.push(Token::Text("{"));
+            rest = &rest[2..];
+        } else if rest.starts_with("}}") {
+            tokens.push(Token::Text("}"));
+            rest = &rest[2..];
+        } else if rest.starts_with('}') {
+            anyhow::bail!("Unexpected '}}' (use '}}}}' for a literal brace)");
+        } else {
+            let end = rest
+                .find('}')
+                .ok_or_else(|| anyhow::anyhow!("Unclosed placeholder: {rest}"))?;
+            let name = &rest[1..end];
+            if !PLACEHOLDERS.contains(&name) {
+                anyhow::bail!(
+                    "Unknown placeholder: {{{name}}} (valid: {})",
+                    PLACEHOLDERS.join(", ")
+                );
+            }
+            tokens.push(Token::Placeholder(name));
+            rest = &rest[end + 1..];
+        }
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    Ok(tokens)
+}
+
+pub fn validate(template: &str) -> anyhow::Result<()> {
+    parse(template).map(|_| ())
+}
+
+// Placeholders missing from the context are rendered as empty strings
+pub fn render(template: &str, ctx: &TemplateContext) -> anyhow::Result<String> {
+    Ok(parse(template)?
+        .into_iter()
+        .map(|t| match t {
+            Token::Text(s) => s,
+            Token::Placeholder(name) => ctx.get(name).unwrap_or(""),
+        })
+        .collect())
+}
+
+// Format UNIX timestamp as "YYYY-MM-DD HH:MM:SS UTC"
+pub fn format_utc(ts: u64) -> String {
+    let (days, secs) = (ts / 86400, ts % 86400);
+    // Civil date from days since epoch (H. Hinnant)
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!(
+        "{y:04}-{m:02}-{d:02} {:02}:{:02}:{:02} UTC",
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+// Render template body using sample context (for live preview)
+pub fn preview_handler(mut request: Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    let mut buf = [0_u8; 512];
+    let len = request.read(&mut buf)?;
+    let template = String::from_utf8_lossy(&buf[0..len]);
+
+    match render(&template, &TemplateContext::sample()) {
+        Ok(s) => {
+            let mut response =
+                request.into_response(200, Some("OK"), &[("Content-Type", "text/plain")])?;
+            response.write(s.as_bytes())?;
+        }
+        Err(e) => {
+            let mut response = request.into_response(
+                400,
+                Some("Invalid Template"),
+                &[("Content-Type", "text/plain")],
+            )?;
+            response.write(e.to_string().as_bytes())?;
+        }
+    }
+    Ok::<(), anyhow::Error>(())
+}
@@ -0,0 +1,35 @@
+use esp_idf_svc::http::server::{EspHttpConnection, Request};
+
+// OS connectivity check URLs (Android, iOS/macOS, Windows, Firefox) - any
+// response other than the expected one triggers the captive portal popup
+pub const PROBE_URLS: [&str; 8] = [
+    "/generate_204",
+    "/gen_204",
+    "/hotspot-detect.html",
+    "/library/test/success.html",
+    "/connecttest.txt",
+    "/ncsi.txt",
+    "/redirect",
+    "/success.txt",
+];
+
+// Redirect to WiFi setup page while captive portal is active
+pub fn captive_handler(request: Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    match crate::wifi::dns::portal_ip() {
+        Some(ip) => {
+            log::info!("Captive portal probe: {}", request.uri());
+            request.into_response(
+                302,
+                Some("Found"),
+                &[
+                    ("Location", &format!("http://{ip}/wifi")),
+                    ("Cache-Control", "no-cache"),
+                ],
+            )?;
+        }
+        None => {
+            request.into_status_response(404)?;
+        }
+    }
+    Ok(())
+}
@@ -3,6 +3,7 @@ use esp_idf_svc::http::server::{
 };
 use esp_idf_svc::http::Method;
 
+mod captive;
 mod flash_msg;
 mod hello;
 mod home_page;
@@ -24,6 +25,7 @@ impl<'a> WebServer<'a> {
         log::info!("Starting HTTPD:");
         let config: HttpConfig = HttpConfig {
             uri_match_wildcard: true,
+            max_uri_handlers: 64,
             ..Default::default()
         };
         let mut server = EspHttpServer::new(&config)?;
@@ -35,6 +37,11 @@ impl<'a> WebServer<'a> {
         server.fn_handler("/reset_page", Method::Get, reset::make_reset_page(navbar))?;
         server.fn_handler("/reset", Method::Get, reset::reset_handler)?;
 
+        // Captive portal probes (AP mode)
+        for url in captive::PROBE_URLS {
+            server.fn_handler(url, Method::Get, captive::captive_handler)?;
+        }
+
         Ok(Self { server })
     }
 
@@ -46,3 +53,23 @@ impl<'a> WebServer<'a> {
         Ok(())
     }
 }
+
+// Read full request body (up to max bytes)
+pub fn read_body(
+    request: &mut Request<&mut EspHttpConnection>,
+    max: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut buf = [0_u8; 256];
+    loop {
+        let len = request.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        if body.len() + len > max {
+            anyhow::bail!("Request body too large (max {max} bytes)");
+        }
+        body.extend_from_slice(&buf[0..len]);
+    }
+    Ok(body)
+}
@@ -1,12 +1,98 @@
 use crate::nvs::NVStore;
+use esp_idf_svc::ipv4;
+use esp_idf_svc::wifi::{AccessPointInfo, AuthMethod, ClientConfiguration};
 use heapless::String;
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    // WPA2/WPA3-Personal (PSK or SAE)
+    #[default]
+    Personal,
+    // WPA3-Personal only (SAE with PMF required)
+    Wpa3,
+    // WPA2/WPA3-Enterprise (EAP-PEAP/TTLS - password is EAP password)
+    Enterprise,
+}
+
+impl Security {
+    // Form value (serde name)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Security::Personal => "personal",
+            Security::Wpa3 => "wpa3",
+            Security::Enterprise => "enterprise",
+        }
+    }
+}
+
+impl std::fmt::Display for Security {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Security::Personal => write!(f, "WPA2/WPA3-Personal"),
+            Security::Wpa3 => write!(f, "WPA3-Personal"),
+            Security::Enterprise => write!(f, "Enterprise"),
+        }
+    }
+}
 
 #[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
 pub struct APConfig {
     pub ssid: String<32>,
     pub password: String<64>,
+    // Static IPv4 settings (DHCP if ip is empty)
+    #[serde(default)]
+    pub ip: String<15>,
+    #[serde(default)]
+    pub netmask: String<15>,
+    #[serde(default)]
+    pub gateway: String<15>,
+    // Comma separated primary/secondary DNS servers
+    #[serde(default)]
+    pub dns: String<31>,
+    // DHCP hostname (default if empty)
+    #[serde(default)]
+    pub hostname: String<30>,
+    // Connection order (highest first)
+    #[serde(default)]
+    pub priority: u8,
+    // Hidden SSID (connect directly - not required in scan results)
+    #[serde(default)]
+    pub hidden: bool,
+    // Only connect to BSSID (aa:bb:cc:dd:ee:ff) / channel if set
+    #[serde(default)]
+    pub bssid: String<17>,
+    #[serde(default)]
+    pub channel: u8,
+    #[serde(default)]
+    pub security: Security,
+    // EAP outer identity (username if empty) / inner username
+    #[serde(default)]
+    pub identity: String<64>,
+    #[serde(default)]
+    pub username: String<64>,
+}
+
+const MAX_FAILURES: u32 = 100;
+
+// Connection history (stored separately so AP edits don't reset it)
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct APStats {
+    // Last successful connection (unix time)
+    pub last_connected: u64,
+    // Failures since last successful connection
+    pub failures: u32,
+}
+
+fn parse_ip(name: &str, ip: &str) -> anyhow::Result<Ipv4Addr> {
+    ip.trim()
+        .parse::<Ipv4Addr>()
+        .map_err(|_| anyhow::anyhow!("Invalid {name}: {ip}"))
 }
 
 impl APConfig {
@@ -18,22 +104,180 @@ impl APConfig {
             password: password
                 .try_into()
                 .map_err(|_| anyhow::anyhow!("Failed to create PW"))?,
+            ..Default::default()
         })
     }
+
+    pub fn is_static(&self) -> bool {
+        !self.ip.is_empty()
+    }
+
+    // STA netif IPv4 configuration
+    pub fn ip_configuration(&self) -> anyhow::Result<ipv4::ClientConfiguration> {
+        if !self
+            .hostname
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            anyhow::bail!("Invalid Hostname: {}", self.hostname);
+        }
+        if !self.is_static() {
+            return Ok(ipv4::ClientConfiguration::DHCP(ipv4::DHCPClientSettings {
+                hostname: (!self.hostname.is_empty()).then(|| self.hostname.clone()),
+            }));
+        }
+        let ip = parse_ip("IP", &self.ip)?;
+        let mask = u32::from(parse_ip("Netmask", &self.netmask)?);
+        // Netmask must be contiguous
+        if mask.leading_ones() != mask.count_ones() {
+            anyhow::bail!("Invalid Netmask: {}", self.netmask);
+        }
+        let gateway = parse_ip("Gateway", &self.gateway)?;
+        let mut dns = self
+            .dns
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| parse_ip("DNS", s));
+        let (primary, secondary) = (dns.next().transpose()?, dns.next().transpose()?);
+        if dns.next().is_some() {
+            anyhow::bail!("Max 2 DNS servers");
+        }
+        Ok(ipv4::ClientConfiguration::Fixed(ipv4::ClientSettings {
+            ip,
+            subnet: ipv4::Subnet {
+                gateway,
+                mask: ipv4::Mask(mask.leading_ones() as u8),
+            },
+            dns: primary,
+            secondary_dns: secondary,
+        }))
+    }
+
+    pub fn bssid(&self) -> anyhow::Result<Option<[u8; 6]>> {
+        if self.bssid.is_empty() {
+            return Ok(None);
+        }
+        let mut bssid = [0_u8; 6];
+        let mut octets = self.bssid.split(':');
+        for b in bssid.iter_mut() {
+            *b = octets
+                .next()
+                .and_then(|o| u8::from_str_radix(o, 16).ok())
+                .ok_or_else(|| anyhow::anyhow!("Invalid BSSID: {}", self.bssid))?;
+        }
+        if octets.next().is_some() {
+            anyhow::bail!("Invalid BSSID: {}", self.bssid);
+        }
+        Ok(Some(bssid))
+    }
+
+    // Check scan result matches SSID (and BSSID/channel if pinned)
+    pub fn matches(&self, ap: &AccessPointInfo) -> bool {
+        ap.ssid == self.ssid
+            && self.bssid().ok().flatten().is_none_or(|b| b == ap.bssid)
+            && (self.channel == 0 || self.channel == ap.channel)
+    }
+
+    pub fn client_configuration(&self) -> anyhow::Result<ClientConfiguration> {
+        let config = ClientConfiguration {
+            ssid: self.ssid.clone(),
+            password: self.password.clone(),
+            bssid: self.bssid()?,
+            channel: (self.channel > 0).then_some(self.channel),
+            ..Default::default()
+        };
+        Ok(match self.security {
+            Security::Personal => config,
+            Security::Wpa3 => ClientConfiguration {
+                auth_method: AuthMethod::WPA3Personal,
+                ..config
+            },
+            // EAP credentials are set separately
+            Security::Enterprise => ClientConfiguration {
+                auth_method: AuthMethod::WPA2Enterprise,
+                password: Default::default(),
+                ..config
+            },
+        })
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match self.security {
+            Security::Personal => {}
+            Security::Wpa3 => {
+                if self.password.len() < 8 {
+                    anyhow::bail!("WPA3 password must be at least 8 characters");
+                }
+            }
+            Security::Enterprise => {
+                if self.username.is_empty() || self.password.is_empty() {
+                    anyhow::bail!("Enterprise username and password required");
+                }
+            }
+        }
+        if self.channel > 13 {
+            anyhow::bail!("Invalid channel: {} (1-13 or 0 for any)", self.channel);
+        }
+        self.bssid()?;
+        self.ip_configuration().map(|_| ())
+    }
 }
 
 pub struct APStore(());
 
+// Each AP is stored under its own (hashed) key with a separate index of SSIDs
+// so the number of APs is not limited by the NVS value size
+const AP_INDEX_KEY: &str = "ap_index";
+// Previous format - all APs in single map (migrated on first access)
+const LEGACY_APS_KEY: &str = "aps";
+
+// NVS keys are limited to 15 chars so SSIDs are hashed (FNV-1a)
+fn hashed_key(prefix: &str, ssid: &str) -> std::string::String {
+    let hash = ssid.bytes().fold(0x811c9dc5_u32, |h, b| {
+        (h ^ b as u32).wrapping_mul(0x01000193)
+    });
+    format!("{prefix}{hash:08x}")
+}
+
 impl APStore {
+    fn ap_key(ssid: &str) -> std::string::String {
+        hashed_key("ap_", ssid)
+    }
+    fn migrate() -> anyhow::Result<()> {
+        let Some(aps) = NVStore::get::<HashMap<heapless::String<32>, APConfig>>(LEGACY_APS_KEY)?
+        else {
+            return Ok(());
+        };
+        let mut index = APStore::index()?;
+        for (ssid, ap) in aps.iter() {
+            NVStore::set(&APStore::ap_key(ssid), ap)?;
+            if !index.contains(ssid) {
+                index.push(ssid.clone());
+            }
+        }
+        NVStore::set(AP_INDEX_KEY, &index)?;
+        NVStore::delete(LEGACY_APS_KEY)?;
+        log::info!("APStore: migrated {} APs", aps.len());
+        Ok(())
+    }
+    fn index() -> anyhow::Result<Vec<heapless::String<32>>> {
+        Ok(NVStore::get::<Vec<heapless::String<32>>>(AP_INDEX_KEY)?.unwrap_or_default())
+    }
     pub fn get_aps() -> anyhow::Result<Vec<APConfig>> {
-        let out = NVStore::get::<HashMap<heapless::String<32>, APConfig>>("aps")?
-            .unwrap_or(HashMap::new());
-        Ok(out.into_values().collect::<Vec<_>>())
+        APStore::migrate()?;
+        let mut aps = Vec::new();
+        for ssid in APStore::index()? {
+            if let Some(ap) = APStore::get_ap(&ssid)? {
+                aps.push(ap);
+            }
+        }
+        Ok(aps)
     }
     pub fn get_ap(ssid: &heapless::String<32>) -> anyhow::Result<Option<APConfig>> {
-        let out = NVStore::get::<HashMap<heapless::String<32>, APConfig>>("aps")?
-            .unwrap_or(HashMap::new());
-        Ok(out.get(ssid).cloned())
+        APStore::migrate()?;
+        // Check SSID in case of hash collision
+        Ok(NVStore::get::<APConfig>(&APStore::ap_key(ssid))?.filter(|ap| &ap.ssid == ssid))
     }
     pub fn get_ap_str(ssid: &str) -> anyhow::Result<Option<APConfig>> {
         let ssid =
@@ -41,20 +285,108 @@ impl APStore {
         APStore::get_ap(&ssid)
     }
     pub fn add_ap(ap: &APConfig) -> anyhow::Result<()> {
-        let mut aps = NVStore::get::<HashMap<heapless::String<32>, APConfig>>("aps")?
-            .unwrap_or(HashMap::new());
-        aps.insert(ap.ssid.clone(), ap.clone());
-        NVStore::set("aps", &aps)?;
+        APStore::migrate()?;
+        let mut index = APStore::index()?;
+        if let Some(other) = index
+            .iter()
+            .find(|s| **s != ap.ssid && APStore::ap_key(s) == APStore::ap_key(&ap.ssid))
+        {
+            anyhow::bail!("SSID conflicts with existing AP: {other}");
+        }
+        NVStore::set(&APStore::ap_key(&ap.ssid), ap)?;
+        if !index.contains(&ap.ssid) {
+            index.push(ap.ssid.clone());
+            NVStore::set(AP_INDEX_KEY, &index)?;
+        }
         Ok(())
     }
     pub fn delete_ap(ssid: &str) -> anyhow::Result<()> {
-        let mut aps = NVStore::get::<HashMap<heapless::String<32>, APConfig>>("aps")?
-            .unwrap_or(HashMap::new());
+        APStore::migrate()?;
         let ssid_owned: heapless::String<32> = ssid
             .try_into()
             .map_err(|_| anyhow::anyhow!("Invaled SSID"))?;
-        aps.remove(&ssid_owned);
-        NVStore::set("aps", &aps)?;
+        let mut index = APStore::index()?;
+        if index.contains(&ssid_owned) {
+            index.retain(|s| *s != ssid_owned);
+            NVStore::set(AP_INDEX_KEY, &index)?;
+            NVStore::delete(&APStore::ap_key(ssid))?;
+        }
+        let mut stats = APStore::get_stats()?;
+        if stats.remove(&ssid_owned).is_some() {
+            NVStore::set("ap_stats", &stats)?;
+        }
+        if APStore::get_ca_cert(ssid)?.is_some() {
+            APStore::delete_ca_cert(ssid)?;
+        }
         Ok(())
     }
+    // Enterprise CA certificate (PEM) - stored per AP under hashed key
+    fn ca_cert_key(ssid: &str) -> std::string::String {
+        hashed_key("eap_ca_", ssid)
+    }
+    pub fn get_ca_cert(ssid: &str) -> anyhow::Result<Option<std::string::String>> {
+        NVStore::get::<std::string::String>(&APStore::ca_cert_key(ssid))
+    }
+    pub fn set_ca_cert(ssid: &str, pem: &str) -> anyhow::Result<()> {
+        let pem = pem.trim().replace("\r\n", "\n");
+        if !pem.starts_with("-----BEGIN CERTIFICATE-----") {
+            anyhow::bail!("Invalid CA certificate (PEM required)");
+        }
+        NVStore::set::<std::string::String>(&APStore::ca_cert_key(ssid), &pem)
+    }
+    pub fn delete_ca_cert(ssid: &str) -> anyhow::Result<()> {
+        NVStore::delete(&APStore::ca_cert_key(ssid))
+    }
+    pub fn get_stats() -> anyhow::Result<HashMap<heapless::String<32>, APStats>> {
+        Ok(
+            NVStore::get::<HashMap<heapless::String<32>, APStats>>("ap_stats")?
+                .unwrap_or(HashMap::new()),
+        )
+    }
+    pub fn record_success(ssid: &heapless::String<32>) -> anyhow::Result<()> {
+        let mut stats = APStore::get_stats()?;
+        let entry = stats.entry(ssid.clone()).or_default();
+        entry.last_connected = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        entry.failures = 0;
+        NVStore::set("ap_stats", &stats)
+    }
+    pub fn record_failure(ssid: &heapless::String<32>) -> anyhow::Result<()> {
+        let mut stats = APStore::get_stats()?;
+        let entry = stats.entry(ssid.clone()).or_default();
+        // Limit NVS writes while AP is unavailable
+        if entry.failures >= MAX_FAILURES {
+            return Ok(());
+        }
+        entry.failures += 1;
+        NVStore::set("ap_stats", &stats)
+    }
+    // Known APs to try in order - visible (or hidden) APs ordered by priority,
+    // failures, most recent connection and signal strength
+    pub fn candidates(known: &[APConfig], visible: &[AccessPointInfo]) -> Vec<APConfig> {
+        let stats = APStore::get_stats().unwrap_or_default();
+        let mut candidates = known
+            .iter()
+            .filter_map(|ap| {
+                let rssi = visible
+                    .iter()
+                    .filter(|v| ap.matches(v))
+                    .map(|v| v.signal_strength)
+                    .max();
+                (rssi.is_some() || ap.hidden).then_some((ap, rssi))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(ap, rssi)| {
+            let stats = stats.get(&ap.ssid).cloned().unwrap_or_default();
+            (
+                Reverse(ap.priority),
+                stats.failures,
+                Reverse(stats.last_connected),
+                Reverse(rssi.unwrap_or(i8::MIN)),
+            )
+        });
+        candidates.into_iter().map(|(ap, _)| ap.clone()).collect()
+    }
 }
@@ -0,0 +1,554 @@
+use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::netif::IpEvent;
+use esp_idf_svc::wifi::WifiEvent;
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::health::{self, Recovery};
+use super::roam::{self, RoamConfig, RoamEvent};
+use super::{
+    ap_station_count, sta_bssid, sta_rssi, APConfig, APStore, FallbackAP, WifiManager, WifiState,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+// Time to wait for disconnect event after aborting connection
+const ABORT_TIMEOUT: Duration = Duration::from_secs(2);
+const BACKOFF_MIN: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+// Reconnect attempts to last AP before rescanning
+const RESCAN_ATTEMPTS: u32 = 5;
+// AP timeout extension while stations connected
+const AP_EXTEND: Duration = Duration::from_secs(60);
+// Retry known APs (in AP+STA mode) while fallback AP is active
+const AP_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+// Keep fallback AP up after station connects (so setup clients can see result)
+const AP_GRACE: Duration = Duration::from_secs(60);
+// RSSI sample interval while online (roaming)
+const ROAM_SAMPLE: Duration = Duration::from_secs(5);
+const TICK: Duration = Duration::from_millis(500);
+
+// Connection thread event channel (for requests from other threads)
+static CONNECTION_TX: Mutex<Option<mpsc::Sender<Event>>> = Mutex::new(None);
+
+// On-demand scan requested (cleared when WIFI_SCAN updated)
+static SCAN_PENDING: AtomicBool = AtomicBool::new(false);
+
+// State change subscribers
+static WIFI_SUBSCRIBERS: Mutex<Vec<mpsc::Sender<WifiState>>> = Mutex::new(Vec::new());
+
+// Subscribe to WifiState changes (published by connection thread)
+pub fn subscribe() -> mpsc::Receiver<WifiState> {
+    let (tx, rx) = mpsc::channel();
+    WIFI_SUBSCRIBERS.lock().unwrap().push(tx);
+    rx
+}
+
+// Request background scan from connection thread (updates WIFI_SCAN). Returns
+// immediately - the scan is made once the connection is in a stable state
+// (poll scan_pending for completion)
+pub fn request_scan() -> anyhow::Result<()> {
+    if CONNECTION_TX.get_cloned()?.is_none() {
+        anyhow::bail!("WiFi connection thread not started");
+    }
+    SCAN_PENDING.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn scan_pending() -> bool {
+    SCAN_PENDING.load(Ordering::Relaxed)
+}
+
+fn publish(state: &WifiState) {
+    log::info!("WifiState: {state}");
+    // Remove closed subscribers
+    WIFI_SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|tx| tx.send(state.clone()).is_ok());
+}
+
+// System event loop events (forwarded to connection thread)
+#[derive(Debug)]
+enum Event {
+    StaConnected,
+    StaDisconnected,
+    GotIp,
+    LostIp,
+    // Health monitor recovery request
+    Recover(Recovery),
+}
+
+#[derive(Debug)]
+enum State {
+    Scanning,
+    Connecting {
+        ap: APConfig,
+        candidates: VecDeque<APConfig>,
+        deadline: Instant,
+        aborting: bool,
+    },
+    Online(APConfig),
+    Backoff(Instant),
+    // Fallback AP (known APs are retried in AP+STA mode at retry time)
+    AccessPoint(Instant),
+    // Waiting for disconnect before reassociating to target AP
+    Roaming {
+        target: APConfig,
+        current: APConfig,
+        deadline: Instant,
+    },
+}
+
+struct Connection {
+    manager: WifiManager<'static>,
+    fallback: Option<FallbackAP>,
+    state: State,
+    // Last AP we were online with (reconnect target)
+    last: Option<APConfig>,
+    attempt: u32,
+    // Fallback AP timeout (returns to station mode)
+    ap_timeout: Option<Instant>,
+    // Fallback AP teardown (after station connected)
+    ap_grace: Option<Instant>,
+    // Roam in progress (AP config we roamed from, event published on success)
+    roam: Option<(APConfig, RoamEvent)>,
+    // Next RSSI sample
+    roam_sample: Instant,
+    // RSSI below roam threshold since
+    roam_low: Option<Instant>,
+    // Last roam scan
+    roam_scan: Option<Instant>,
+}
+
+impl WifiManager<'static> {
+    // Start connection thread - connects to strongest known AP (falling back
+    // to fallback AP if no known AP is available on startup) and reconnects with
+    // exponential backoff. State changes are published to subscribers.
+    pub fn start(
+        self,
+        sys_loop: &EspSystemEventLoop,
+        fallback: Option<FallbackAP>,
+    ) -> anyhow::Result<()> {
+        let (tx, rx) = mpsc::channel::<Event>();
+
+        // Event loop callbacks must not block - forward to connection thread
+        let wifi_tx = tx.clone();
+        let wifi_subscription = sys_loop.subscribe::<WifiEvent, _>(move |event| {
+            let event = match event {
+                WifiEvent::StaConnected(_) => Event::StaConnected,
+                WifiEvent::StaDisconnected(_) => Event::StaDisconnected,
+                _ => return,
+            };
+            wifi_tx.send(event).unwrap_or(());
+        })?;
+        let ip_subscription = sys_loop.subscribe::<IpEvent, _>(move |event| {
+            let event = match event {
+                IpEvent::DhcpIpAssigned(_) => Event::GotIp,
+                IpEvent::DhcpIpDeassigned(_) => Event::LostIp,
+                _ => return,
+            };
+            tx.send(event).unwrap_or(());
+        })?;
+
+        CONNECTION_TX.replace(Some(tx.clone()))?;
+
+        // Connectivity monitor (subscribed before thread publishes state)
+        let health_tx = tx.clone();
+        health::start(subscribe(), move |recovery| {
+            health_tx.send(Event::Recover(recovery)).unwrap_or(())
+        })?;
+
+        let mut connection = Connection {
+            manager: self,
+            fallback,
+            state: State::Scanning,
+            last: None,
+            attempt: 0,
+            ap_timeout: None,
+            ap_grace: None,
+            roam: None,
+            roam_sample: Instant::now(),
+            roam_low: None,
+            roam_scan: None,
+        };
+
+        thread::Builder::new().stack_size(8192).spawn(move || {
+            // Keep subscriptions alive for lifetime of thread
+            let _subscriptions = (wifi_subscription, ip_subscription);
+            publish(&WifiState::NotConnected);
+            connection.scan();
+            loop {
+                match rx.recv_timeout(TICK) {
+                    Ok(event) => connection.handle_event(event),
+                    Err(mpsc::RecvTimeoutError::Timeout) => connection.tick(),
+                    Err(e) => {
+                        log::error!("WiFi event channel closed: {e}");
+                        break;
+                    }
+                }
+            }
+        })?;
+        Ok(())
+    }
+}
+
+impl Connection {
+    fn handle_event(&mut self, event: Event) {
+        log::debug!("WiFi Event: {event:?} [{:?}]", self.state);
+        match (&self.state, event) {
+            (State::Connecting { ap, .. }, Event::StaConnected) => {
+                log::info!("WiFi Associated: {} (waiting for IP)", ap.ssid);
+            }
+            (State::Connecting { ap, .. }, Event::GotIp) => {
+                // Keep unpinned AP config after roaming (so reconnects can use
+                // any BSSID)
+                let ap = match self.roam.take() {
+                    Some((current, event)) => {
+                        roam::publish(event);
+                        current
+                    }
+                    None => ap.clone(),
+                };
+                match self.manager.wifi.sta_netif().get_ip_info() {
+                    Ok(ip_info) => {
+                        self.attempt = 0;
+                        self.roam_low = None;
+                        self.last = Some(ap.clone());
+                        if let Err(e) = APStore::record_success(&ap.ssid) {
+                            log::error!("Failed to update AP stats: {e}");
+                        }
+                        if self.manager.is_ap_active() {
+                            log::info!(
+                                "WiFi Connected: stopping fallback AP in {}s",
+                                AP_GRACE.as_secs()
+                            );
+                            self.ap_grace = Some(Instant::now() + AP_GRACE);
+                        }
+                        self.state = State::Online(ap.clone());
+                        publish(&WifiState::Station(ap, ip_info));
+                    }
+                    Err(e) => {
+                        log::error!("WiFi Error: {e}");
+                        self.next_candidate();
+                    }
+                }
+            }
+            (State::Connecting { .. }, Event::StaDisconnected) => {
+                self.next_candidate();
+            }
+            (State::Roaming { .. }, Event::StaDisconnected) => {
+                self.roam_connect();
+            }
+            (State::Online(ap), Event::Recover(recovery)) => {
+                log::error!("WiFi Recovery: {recovery:?} [{}]", ap.ssid);
+                if let Err(e) = self.recover(recovery) {
+                    log::error!("WiFi Error: {e}");
+                }
+                publish(&WifiState::NotConnected);
+                // Disconnect event is ignored while in backoff (reconnects to
+                // last AP)
+                self.state = State::Backoff(Instant::now() + ABORT_TIMEOUT);
+            }
+            (State::Online(ap), Event::StaDisconnected | Event::LostIp) => {
+                log::error!("WiFi Disconnected: {}", ap.ssid);
+                publish(&WifiState::NotConnected);
+                self.backoff();
+            }
+            _ => {}
+        }
+    }
+
+    // Check timeouts
+    fn tick(&mut self) {
+        // On-demand scan without changing configuration (not while connecting)
+        if SCAN_PENDING.load(Ordering::Relaxed)
+            && matches!(
+                self.state,
+                State::Online(_) | State::AccessPoint(_) | State::Backoff(_)
+            )
+        {
+            if let Err(e) = self.manager.scan_visible() {
+                log::error!("WiFi Scan Error: {e}");
+            }
+            SCAN_PENDING.store(false, Ordering::Relaxed);
+        }
+        if self.ap_grace.is_some_and(|until| Instant::now() >= until) {
+            self.ap_grace = None;
+            self.ap_timeout = None;
+            // Only stop AP if still connected
+            if let State::Online(_) = self.state {
+                if let Err(e) = self.manager.stop_ap() {
+                    log::error!("WiFi AP Error: {e}");
+                }
+            }
+        }
+        match &self.state {
+            State::Connecting {
+                ap,
+                deadline,
+                aborting,
+                ..
+            } if Instant::now() >= *deadline => {
+                if *aborting {
+                    self.next_candidate();
+                } else {
+                    // Wait for disconnect event (so this is not received while
+                    // connecting to next candidate)
+                    log::error!("WiFi Connection Timeout: {}", ap.ssid);
+                    let _ = self.manager.wifi.disconnect();
+                    if let State::Connecting {
+                        deadline, aborting, ..
+                    } = &mut self.state
+                    {
+                        *deadline = Instant::now() + ABORT_TIMEOUT;
+                        *aborting = true;
+                    }
+                }
+            }
+            State::AccessPoint(retry) => {
+                let now = Instant::now();
+                if self.ap_timeout.is_some_and(|until| now >= until) {
+                    // Don't disconnect stations (setup in progress) or leave AP
+                    // mode if there are no known APs to connect to
+                    if ap_station_count() > 0 || APStore::get_aps().unwrap_or_default().is_empty() {
+                        self.ap_timeout = Some(now + AP_EXTEND);
+                    } else {
+                        log::info!("WiFi AP Timeout: returning to station mode");
+                        if let Err(e) = self.manager.stop_ap() {
+                            log::error!("WiFi AP Error: {e}");
+                        }
+                        publish(&WifiState::NotConnected);
+                        self.scan();
+                    }
+                } else if now >= *retry {
+                    // Scanning/connecting changes channel so skip while stations
+                    // are connected
+                    if ap_station_count() > 0 {
+                        self.state = State::AccessPoint(now + AP_RETRY_INTERVAL);
+                    } else {
+                        log::info!("WiFi AP: retrying known APs");
+                        self.scan();
+                    }
+                }
+            }
+            State::Online(_) => self.check_roam(),
+            State::Roaming { deadline, .. } if Instant::now() >= *deadline => {
+                self.roam_connect();
+            }
+            State::Backoff(until) if Instant::now() >= *until => {
+                // Retry last AP (if any) before rescanning
+                match self.last.clone() {
+                    Some(ap) if self.attempt < RESCAN_ATTEMPTS => self.connect(ap, VecDeque::new()),
+                    _ => self.scan(),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Scan and try visible known APs (strongest first)
+    fn scan(&mut self) {
+        self.state = State::Scanning;
+        if let Err(e) = self.manager.scan() {
+            log::error!("WiFi Scan Error: {e}");
+            self.backoff();
+            return;
+        }
+        SCAN_PENDING.store(false, Ordering::Relaxed);
+        let mut candidates = VecDeque::from(APStore::candidates(
+            &APStore::get_aps().unwrap_or_default(),
+            self.manager.visible.as_deref().unwrap_or_default(),
+        ));
+        match candidates.pop_front() {
+            Some(ap) => self.connect(ap, candidates),
+            None => self.failed(),
+        }
+    }
+
+    fn connect(&mut self, ap: APConfig, candidates: VecDeque<APConfig>) {
+        log::info!("WiFi Connecting: {} [attempt {}]", ap.ssid, self.attempt);
+        // Connection is asynchronous (completes on GotIp event)
+        let result = self.start_sta(&ap);
+        self.state = State::Connecting {
+            ap,
+            candidates,
+            deadline: Instant::now() + CONNECT_TIMEOUT,
+            aborting: false,
+        };
+        if let Err(e) = result {
+            log::error!("WiFi Error: {e}");
+            self.next_candidate();
+        }
+    }
+
+    fn start_sta(&mut self, ap: &APConfig) -> anyhow::Result<()> {
+        self.manager.configure_sta(ap)?;
+        // Allow AP steering (802.11k/v)
+        if RoamConfig::get().is_ok_and(|config| config.enabled) {
+            if let Err(e) = roam::enable_11kv() {
+                log::error!("WiFi 802.11k/v Error: {e}");
+            }
+        }
+        self.manager.wifi.start()?;
+        self.manager.wifi.connect()?;
+        Ok(())
+    }
+
+    fn recover(&mut self, recovery: Recovery) -> anyhow::Result<()> {
+        match recovery {
+            Recovery::Reconnect => self.manager.wifi.disconnect()?,
+            // Restart WiFi (station only)
+            _ => {
+                self.ap_grace = None;
+                self.manager.stop_ap()?;
+                self.manager.wifi.stop()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Sample RSSI and roam to known AP with better signal if below threshold
+    fn check_roam(&mut self) {
+        let now = Instant::now();
+        if now < self.roam_sample {
+            return;
+        }
+        self.roam_sample = now + ROAM_SAMPLE;
+        let config = match RoamConfig::get() {
+            Ok(config) if config.enabled => config,
+            Ok(_) => return,
+            Err(e) => {
+                log::error!("WiFi Roam Config Error: {e}");
+                return;
+            }
+        };
+        // Scanning changes channel - skip while fallback AP is up
+        if self.manager.is_ap_active() {
+            return;
+        }
+        let (Some(bssid), Some(rssi)) = (sta_bssid(), sta_rssi()) else {
+            return;
+        };
+        if rssi >= config.threshold {
+            self.roam_low = None;
+            return;
+        }
+        let low = *self.roam_low.get_or_insert(now);
+        if now < low + Duration::from_secs(config.low_duration as u64)
+            || self
+                .roam_scan
+                .is_some_and(|scan| now < scan + Duration::from_secs(config.scan_interval as u64))
+        {
+            return;
+        }
+        self.roam_scan = Some(now);
+        log::info!(
+            "WiFi Roam: RSSI {rssi} dBm (threshold {} dBm) - scanning",
+            config.threshold
+        );
+        if let Err(e) = self.manager.scan_visible() {
+            log::error!("WiFi Scan Error: {e}");
+            return;
+        }
+        let target = config
+            .target(
+                &APStore::get_aps().unwrap_or_default(),
+                self.manager.visible.as_deref().unwrap_or_default(),
+                bssid,
+                rssi,
+            )
+            .map(|(ap, info)| {
+                let event = RoamEvent::new(&ap.ssid, (bssid, rssi), info);
+                (ap, event)
+            });
+        let State::Online(current) = &self.state else {
+            return;
+        };
+        match target {
+            Some((target, event)) => {
+                log::info!("WiFi Roaming: {event}");
+                let current = current.clone();
+                self.roam = Some((current.clone(), event));
+                // Wait for disconnect event (so this is not received while
+                // connecting to target)
+                let _ = self.manager.wifi.disconnect();
+                self.state = State::Roaming {
+                    target,
+                    current,
+                    deadline: now + ABORT_TIMEOUT,
+                };
+            }
+            None => log::info!("WiFi Roam: no better AP found"),
+        }
+    }
+
+    // Connect to roam target (falling back to current AP config if this fails)
+    fn roam_connect(&mut self) {
+        if let State::Roaming {
+            target, current, ..
+        } = std::mem::replace(&mut self.state, State::Scanning)
+        {
+            self.connect(target, VecDeque::from([current]));
+        }
+    }
+
+    fn next_candidate(&mut self) {
+        if let State::Connecting { ap, candidates, .. } = &mut self.state {
+            // Failed roam is not an AP failure (retry current AP config)
+            if let Some((_, event)) = self.roam.take() {
+                log::error!("WiFi Roam Failed: {event}");
+            } else if let Err(e) = APStore::record_failure(&ap.ssid) {
+                log::error!("Failed to update AP stats: {e}");
+            }
+            if let Some(ap) = candidates.pop_front() {
+                let candidates = std::mem::take(candidates);
+                self.connect(ap, candidates);
+                return;
+            }
+        }
+        self.failed();
+    }
+
+    // No AP available - start fallback AP if we have never connected (or
+    // return to AP state if already active)
+    fn failed(&mut self) {
+        if self.manager.is_ap_active() {
+            self.state = State::AccessPoint(Instant::now() + AP_RETRY_INTERVAL);
+            return;
+        }
+        match self.fallback.clone() {
+            Some(fallback) if self.last.is_none() => {
+                match self.manager.start_fallback_ap(&fallback) {
+                    Ok(state) => {
+                        self.ap_timeout = (fallback.timeout > 0)
+                            .then(|| Instant::now() + Duration::from_secs(fallback.timeout as u64));
+                        self.state = State::AccessPoint(Instant::now() + AP_RETRY_INTERVAL);
+                        publish(&state);
+                    }
+                    Err(e) => {
+                        log::error!("WiFi AP Error: {e}");
+                        self.backoff();
+                    }
+                }
+            }
+            _ => self.backoff(),
+        }
+    }
+
+    fn backoff(&mut self) {
+        let delay = BACKOFF_MIN
+            .saturating_mul(1 << self.attempt.min(6))
+            .min(BACKOFF_MAX);
+        log::info!(
+            "WiFi Reconnect: {}s [attempt {}]",
+            delay.as_secs(),
+            self.attempt
+        );
+        self.attempt += 1;
+        self.state = State::Backoff(Instant::now() + delay);
+    }
+}
@@ -0,0 +1,121 @@
+use std::net::{Ipv4Addr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Captive portal DNS server - answers all A queries with the AP IP (so any
+// hostname resolves to the device while in AP mode)
+
+const DNS_PORT: u16 = 53;
+const DNS_TTL: u32 = 60;
+const DNS_MAX_LEN: usize = 512;
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+const TYPE_A: u16 = 1;
+const CLASS_IN: u16 = 1;
+
+static DNS_RUNNING: AtomicBool = AtomicBool::new(false);
+static PORTAL_IP: Mutex<Option<Ipv4Addr>> = Mutex::new(None);
+static DNS_THREAD: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+// Portal IP (None if captive portal not active)
+pub fn portal_ip() -> Option<Ipv4Addr> {
+    PORTAL_IP.get_cloned().ok().flatten()
+}
+
+// Start DNS server (does nothing if already running)
+pub fn start(ip: Ipv4Addr) -> anyhow::Result<()> {
+    PORTAL_IP.replace(Some(ip))?;
+    if DNS_RUNNING.swap(true, Ordering::Relaxed) {
+        return Ok(());
+    }
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DNS_PORT)).inspect_err(|_| {
+        DNS_RUNNING.store(false, Ordering::Relaxed);
+    })?;
+    socket.set_read_timeout(Some(READ_TIMEOUT))?;
+    log::info!("Captive portal DNS started: {ip}");
+    let handle = thread::Builder::new().stack_size(4096).spawn(move || {
+        let mut buf = [0_u8; DNS_MAX_LEN];
+        while DNS_RUNNING.load(Ordering::Relaxed) {
+            // Read timeout allows stop flag to be checked
+            let Ok((len, src)) = socket.recv_from(&mut buf) else {
+                continue;
+            };
+            let Some(ip) = portal_ip() else {
+                continue;
+            };
+            if let Some(response) = response(&buf[..len], ip) {
+                if let Err(e) = socket.send_to(&response, src) {
+                    log::error!("DNS Error: {e}");
+                }
+            }
+        }
+        log::info!("Captive portal DNS stopped");
+    })?;
+    DNS_THREAD.replace(Some(handle))?;
+    Ok(())
+}
+
+// Stop DNS server and wait for thread to exit (so port is released before
+// any restart)
+pub fn stop() {
+    let _ = PORTAL_IP.replace(None);
+    DNS_RUNNING.store(false, Ordering::Relaxed);
+    if let Ok(Some(handle)) = DNS_THREAD.replace(None) {
+        let _ = handle.join();
+    }
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *buf.get(offset)?,
+        *buf.get(offset + 1)?,
+    ]))
+}
+
+// Build response for single question query (None if query is invalid)
+fn response(query: &[u8], ip: Ipv4Addr) -> Option<Vec<u8>> {
+    let flags = read_u16(query, 2)?;
+    let qdcount = read_u16(query, 4)?;
+    // Ignore responses and non-standard queries
+    if flags & 0x8000 != 0 || (flags >> 11) & 0xf != 0 || qdcount != 1 {
+        return None;
+    }
+    // Question name (labels terminated by zero length)
+    let mut offset = 12;
+    loop {
+        let len = *query.get(offset)? as usize;
+        if len == 0 {
+            offset += 1;
+            break;
+        }
+        if len & 0xc0 != 0 {
+            return None;
+        }
+        offset += len + 1;
+    }
+    let qtype = read_u16(query, offset)?;
+    let qclass = read_u16(query, offset + 2)?;
+    let question = &query[12..offset + 4];
+    let answer = qtype == TYPE_A && qclass == CLASS_IN;
+
+    let mut response = Vec::with_capacity(12 + question.len() + 16);
+    response.extend_from_slice(&query[0..2]);
+    // Response + Authoritative + Recursion Desired (from query) + Recursion Available
+    response.extend_from_slice(&(0x8480 | (flags & 0x0100)).to_be_bytes());
+    response.extend_from_slice(&1_u16.to_be_bytes());
+    response.extend_from_slice(&(answer as u16).to_be_bytes());
+    response.extend_from_slice(&[0, 0, 0, 0]);
+    response.extend_from_slice(question);
+    if answer {
+        // Name is pointer to question
+        response.extend_from_slice(&0xc00c_u16.to_be_bytes());
+        response.extend_from_slice(&TYPE_A.to_be_bytes());
+        response.extend_from_slice(&CLASS_IN.to_be_bytes());
+        response.extend_from_slice(&DNS_TTL.to_be_bytes());
+        response.extend_from_slice(&4_u16.to_be_bytes());
+        response.extend_from_slice(&ip.octets());
+    }
+    Some(response)
+}
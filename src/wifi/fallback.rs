@@ -0,0 +1,155 @@
+use esp_idf_svc::sys::{esp, esp_random, esp_wifi_set_country_code, esp_wifi_set_max_tx_power};
+use heapless::String;
+use serde::{Deserialize, Serialize};
+
+use std::ffi::CString;
+
+use super::{default_name, APConfig};
+use crate::nvs::NVStore;
+
+// Fallback (setup) AP started when no known AP is available. Defaults are
+// generated per device (SSID from MAC, random password) and saved to NVS.
+
+const FALLBACK_NVS_KEY: &str = "fallback_ap";
+
+const PASSWORD_LEN: usize = 10;
+// Avoid ambiguous characters (0/O, 1/l/I) as password is read from serial
+const PASSWORD_CHARS: &[u8] = b"abcdefghijkmnpqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct FallbackAP {
+    pub ssid: String<32>,
+    pub password: String<64>,
+    #[serde(default = "default_channel")]
+    pub channel: u8,
+    // ISO 3166 country code ("01" for world safe mode)
+    #[serde(default = "default_country")]
+    pub country: String<2>,
+    // Max TX power (dBm)
+    #[serde(default = "default_tx_power")]
+    pub tx_power: u8,
+    // Return to station scanning after timeout (secs - 0 to stay in AP mode)
+    #[serde(default = "default_timeout")]
+    pub timeout: u32,
+    // WPA2/WPA3 transition mode (WPA2 only if not set)
+    #[serde(default)]
+    pub wpa3: bool,
+    // Captive portal DNS (resolve all hostnames to AP so clients show the
+    // setup page - enabled unless explicitly disabled)
+    #[serde(default = "default_captive_portal")]
+    pub captive_portal: bool,
+}
+
+fn default_channel() -> u8 {
+    1
+}
+
+fn default_country() -> String<2> {
+    String::try_from("01").unwrap_or_default()
+}
+
+fn default_tx_power() -> u8 {
+    20
+}
+
+fn default_timeout() -> u32 {
+    300
+}
+
+fn default_captive_portal() -> bool {
+    true
+}
+
+fn random_password() -> String<64> {
+    (0..PASSWORD_LEN)
+        .map(|_| {
+            let r = unsafe { esp_random() } as usize;
+            PASSWORD_CHARS[r % PASSWORD_CHARS.len()] as char
+        })
+        .collect()
+}
+
+impl FallbackAP {
+    // Load from NVS (generating and saving per-device defaults if not set)
+    pub fn load(prefix: &str) -> anyhow::Result<Self> {
+        if let Some(config) = Self::get()? {
+            return Ok(config);
+        }
+        let config = Self {
+            ssid: String::try_from(default_name(prefix).as_str())
+                .map_err(|_| anyhow::anyhow!("Invalid SSID"))?,
+            password: random_password(),
+            channel: default_channel(),
+            country: default_country(),
+            tx_power: default_tx_power(),
+            timeout: default_timeout(),
+            wpa3: false,
+            captive_portal: default_captive_portal(),
+        };
+        NVStore::set::<FallbackAP>(FALLBACK_NVS_KEY, &config)?;
+        Ok(config)
+    }
+
+    pub fn get() -> anyhow::Result<Option<Self>> {
+        NVStore::get::<FallbackAP>(FALLBACK_NVS_KEY)
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.validate()?;
+        NVStore::set::<FallbackAP>(FALLBACK_NVS_KEY, self)
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.ssid.is_empty() {
+            anyhow::bail!("SSID required");
+        }
+        if !self.password.is_empty() && self.password.len() < 8 {
+            anyhow::bail!("Password must be at least 8 characters");
+        }
+        if self.country.len() != 2 || !self.country.chars().all(|c| c.is_ascii_alphanumeric()) {
+            anyhow::bail!("Invalid country code: {}", self.country);
+        }
+        let max_channel = self.max_channel();
+        if !(1..=max_channel).contains(&self.channel) {
+            anyhow::bail!(
+                "Invalid channel: {} (1-{max_channel} for country {})",
+                self.channel,
+                self.country
+            );
+        }
+        if !(2..=20).contains(&self.tx_power) {
+            anyhow::bail!("Invalid TX power: {} (2-20 dBm)", self.tx_power);
+        }
+        Ok(())
+    }
+
+    // Highest 2.4GHz channel allowed for AP in country (world safe mode and
+    // North America are limited to 1-11)
+    pub fn max_channel(&self) -> u8 {
+        match self.country.to_ascii_uppercase().as_str() {
+            "01" | "US" | "CA" => 11,
+            _ => 13,
+        }
+    }
+
+    pub fn ap_config(&self) -> APConfig {
+        APConfig {
+            ssid: self.ssid.clone(),
+            password: self.password.clone(),
+            ..Default::default()
+        }
+    }
+
+    // Set country code (before start)
+    pub(super) fn set_country(&self) -> anyhow::Result<()> {
+        let country = CString::new(self.country.to_ascii_uppercase())?;
+        esp!(unsafe { esp_wifi_set_country_code(country.as_ptr(), false) })?;
+        Ok(())
+    }
+
+    // Set TX power (after start - units are 0.25 dBm)
+    pub(super) fn set_tx_power(&self) -> anyhow::Result<()> {
+        esp!(unsafe { esp_wifi_set_max_tx_power((self.tx_power * 4) as i8) })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,332 @@
+use esp_idf_svc::ipv4::IpInfo;
+use esp_idf_svc::ping::{self, EspPing};
+use heapless::String;
+use serde::{Deserialize, Serialize};
+
+use std::net::{Ipv4Addr, TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::WifiState;
+use crate::nvs::NVStore;
+
+// Connectivity monitor - association does not guarantee a working IP path so
+// while in station mode we periodically ping the gateway, resolve a DNS name
+// and (optionally) connect to a TCP endpoint (eg. MQTT broker or HTTP server).
+// Consecutive failed checks (all checks failing unless escalate_degraded is
+// set) trigger the recovery ladder (reconnect, restart WiFi, reboot). The
+// monitor is disabled by default.
+
+const HEALTH_NVS_KEY: &str = "health";
+const PING_COUNT: u32 = 3;
+const PING_TIMEOUT: Duration = Duration::from_secs(1);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// Re-check state while not in station mode
+const IDLE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct HealthConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // Check interval (secs)
+    #[serde(default = "default_interval")]
+    pub interval: u32,
+    #[serde(default)]
+    pub ping_gateway: bool,
+    // Hostname to resolve (empty to skip)
+    #[serde(default)]
+    pub dns_host: String<64>,
+    // TCP endpoint <host>:<port> (empty to skip)
+    #[serde(default)]
+    pub endpoint: String<64>,
+    // Recovery ladder - consecutive failed checks before each action (0 to skip)
+    #[serde(default = "default_reconnect_after")]
+    pub reconnect_after: u32,
+    #[serde(default = "default_restart_after")]
+    pub restart_after: u32,
+    #[serde(default = "default_reboot_after")]
+    pub reboot_after: u32,
+    // Count degraded (some checks failed) as a failure - otherwise only
+    // escalate when all checks fail
+    #[serde(default)]
+    pub escalate_degraded: bool,
+}
+
+fn default_interval() -> u32 {
+    60
+}
+
+fn default_reconnect_after() -> u32 {
+    3
+}
+
+fn default_restart_after() -> u32 {
+    6
+}
+
+fn default_reboot_after() -> u32 {
+    10
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: default_interval(),
+            ping_gateway: true,
+            dns_host: String::new(),
+            endpoint: String::new(),
+            reconnect_after: default_reconnect_after(),
+            restart_after: default_restart_after(),
+            reboot_after: default_reboot_after(),
+            escalate_degraded: false,
+        }
+    }
+}
+
+impl HealthConfig {
+    pub fn get() -> anyhow::Result<Self> {
+        Ok(NVStore::get::<HealthConfig>(HEALTH_NVS_KEY)?.unwrap_or_default())
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.validate()?;
+        NVStore::set::<HealthConfig>(HEALTH_NVS_KEY, self)
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.interval < 10 {
+            anyhow::bail!("Invalid interval: {} (min 10s)", self.interval);
+        }
+        if !self.endpoint.is_empty() {
+            match self.endpoint.rsplit_once(':') {
+                Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {}
+                _ => anyhow::bail!("Invalid endpoint: {} (<host>:<port>)", self.endpoint),
+            }
+        }
+        // Steps must escalate
+        let steps = [self.reconnect_after, self.restart_after, self.reboot_after]
+            .into_iter()
+            .filter(|n| *n > 0)
+            .collect::<Vec<_>>();
+        if steps.windows(2).any(|w| w[0] >= w[1]) {
+            anyhow::bail!("Recovery steps must be in increasing order");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkHealth {
+    // Not checked (monitor disabled or not in station mode)
+    #[default]
+    Unknown,
+    Healthy,
+    // Some checks failed
+    Degraded,
+    // All checks failed
+    Down,
+}
+
+impl std::fmt::Display for NetworkHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NetworkHealth::Unknown => write!(f, "Unknown"),
+            NetworkHealth::Healthy => write!(f, "Healthy"),
+            NetworkHealth::Degraded => write!(f, "Degraded"),
+            NetworkHealth::Down => write!(f, "Down"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Recovery {
+    Reconnect,
+    Restart,
+    Reboot,
+}
+
+impl std::fmt::Display for Recovery {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Recovery::Reconnect => write!(f, "Reconnect"),
+            Recovery::Restart => write!(f, "Restart WiFi"),
+            Recovery::Reboot => write!(f, "Reboot"),
+        }
+    }
+}
+
+// Check results (None if check not enabled)
+#[derive(Clone, Serialize, Debug, Default, PartialEq, Eq)]
+pub struct HealthStatus {
+    pub state: NetworkHealth,
+    pub gateway: Option<bool>,
+    pub dns: Option<bool>,
+    pub endpoint: Option<bool>,
+    // Last check (unix time)
+    pub last_check: u64,
+    // Consecutive failed checks
+    pub failures: u32,
+    pub last_recovery: Option<Recovery>,
+}
+
+static HEALTH_STATUS: Mutex<Option<HealthStatus>> = Mutex::new(None);
+
+pub fn status() -> HealthStatus {
+    HEALTH_STATUS
+        .get_cloned()
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn ping(ip: Ipv4Addr) -> bool {
+    let config = ping::Configuration {
+        count: PING_COUNT,
+        timeout: PING_TIMEOUT,
+        ..Default::default()
+    };
+    match EspPing::default().ping(ip, &config) {
+        Ok(summary) => summary.received > 0,
+        Err(e) => {
+            log::error!("Ping Error: {e}");
+            false
+        }
+    }
+}
+
+fn resolve(host: &str) -> bool {
+    (host, 0)
+        .to_socket_addrs()
+        .is_ok_and(|mut addrs| addrs.next().is_some())
+}
+
+fn connect(endpoint: &str) -> bool {
+    endpoint
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .is_some_and(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).is_ok())
+}
+
+fn check(config: &HealthConfig, ip_info: &IpInfo) -> HealthStatus {
+    let gateway = config.ping_gateway.then(|| ping(ip_info.subnet.gateway));
+    let dns = (!config.dns_host.is_empty()).then(|| resolve(&config.dns_host));
+    let endpoint = (!config.endpoint.is_empty()).then(|| connect(&config.endpoint));
+    let results = [gateway, dns, endpoint]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    let state = if results.iter().all(|ok| *ok) {
+        NetworkHealth::Healthy
+    } else if results.iter().any(|ok| *ok) {
+        NetworkHealth::Degraded
+    } else {
+        NetworkHealth::Down
+    };
+    HealthStatus {
+        state,
+        gateway,
+        dns,
+        endpoint,
+        last_check: unix_time(),
+        ..Default::default()
+    }
+}
+
+// Recovery action for number of consecutive failed checks
+fn recovery(config: &HealthConfig, failures: u32) -> Option<Recovery> {
+    [
+        (config.reboot_after, Recovery::Reboot),
+        (config.restart_after, Recovery::Restart),
+        (config.reconnect_after, Recovery::Reconnect),
+    ]
+    .into_iter()
+    .find(|(after, _)| *after > 0 && failures == *after)
+    .map(|(_, recovery)| recovery)
+}
+
+// Start monitor thread - reconnect/restart requests are sent to connection
+// thread (reboot is handled here)
+pub(super) fn start<F>(wifi_rx: mpsc::Receiver<WifiState>, recover: F) -> anyhow::Result<()>
+where
+    F: Fn(Recovery) + Send + 'static,
+{
+    thread::Builder::new().stack_size(6144).spawn(move || {
+        let mut wifi_state = WifiState::NotConnected;
+        let mut failures = 0;
+        let mut last_recovery = None;
+        loop {
+            while let Ok(state) = wifi_rx.try_recv() {
+                wifi_state = state;
+            }
+            let config = HealthConfig::get().unwrap_or_default();
+            let ip_info = match &wifi_state {
+                WifiState::Station(_, ip_info) if config.enabled => ip_info,
+                _ => {
+                    if !config.enabled {
+                        failures = 0;
+                    }
+                    // Keep recovery state (failures continue after reconnect)
+                    let _ = HEALTH_STATUS.replace(Some(HealthStatus {
+                        failures,
+                        last_recovery,
+                        ..Default::default()
+                    }));
+                    thread::sleep(IDLE_INTERVAL);
+                    continue;
+                }
+            };
+            let mut status = check(&config, ip_info);
+            let failed = match status.state {
+                NetworkHealth::Down => true,
+                NetworkHealth::Degraded => config.escalate_degraded,
+                _ => false,
+            };
+            if status.state == NetworkHealth::Degraded && !failed {
+                log::warn!(
+                    "Network {}: gateway: {:?} dns: {:?} endpoint: {:?}",
+                    status.state,
+                    status.gateway,
+                    status.dns,
+                    status.endpoint
+                );
+            }
+            if !failed {
+                failures = 0;
+            } else {
+                failures += 1;
+                log::error!(
+                    "Network {}: gateway: {:?} dns: {:?} endpoint: {:?} [{failures}]",
+                    status.state,
+                    status.gateway,
+                    status.dns,
+                    status.endpoint
+                );
+                if let Some(action) = recovery(&config, failures) {
+                    log::error!("Network Recovery: {action:?}");
+                    last_recovery = Some(action);
+                    match action {
+                        Recovery::Reboot => esp_idf_hal::reset::restart(),
+                        _ => recover(action),
+                    }
+                }
+            }
+            status.failures = failures;
+            status.last_recovery = last_recovery;
+            let _ = HEALTH_STATUS.replace(Some(status));
+            thread::sleep(Duration::from_secs(config.interval as u64));
+        }
+    })?;
+    Ok(())
+}
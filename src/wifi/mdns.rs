@@ -0,0 +1,96 @@
+use esp_idf_svc::mdns::EspMdns;
+
+use std::sync::Mutex;
+
+use crate::nvs::NVStore;
+
+// mDNS responder - device answers to <hostname>.local and advertises
+// registered services (requires espressif/mdns component)
+
+const HOSTNAME_NVS_KEY: &str = "hostname";
+
+static MDNS: Mutex<Option<EspMdns>> = Mutex::new(None);
+static DEFAULT_HOSTNAME: Mutex<String> = Mutex::new(String::new());
+
+pub struct Mdns(());
+
+impl Mdns {
+    // Start responder using stored hostname (or default if not set)
+    pub fn init(default_hostname: &str) -> anyhow::Result<String> {
+        DEFAULT_HOSTNAME.replace(default_hostname.to_ascii_lowercase())?;
+        let hostname = Self::hostname()?;
+        let mut mdns = EspMdns::take()?;
+        mdns.set_hostname(&hostname)?;
+        mdns.set_instance_name(&hostname)?;
+        MDNS.replace(Some(mdns))?;
+        log::info!("mDNS started: {hostname}.local");
+        Ok(hostname)
+    }
+
+    pub fn hostname() -> anyhow::Result<String> {
+        match NVStore::get::<String>(HOSTNAME_NVS_KEY)? {
+            Some(hostname) => Ok(hostname),
+            None => Ok(DEFAULT_HOSTNAME.get_cloned()?),
+        }
+    }
+
+    // Update and save hostname (empty resets to default)
+    pub fn set_hostname(hostname: &str) -> anyhow::Result<()> {
+        let hostname = hostname.trim().to_ascii_lowercase();
+        if hostname.is_empty() {
+            NVStore::delete(HOSTNAME_NVS_KEY)?;
+        } else {
+            validate_hostname(&hostname)?;
+            NVStore::set::<String>(HOSTNAME_NVS_KEY, &hostname)?;
+        }
+        let hostname = Self::hostname()?;
+        Self::with_mdns(|mdns| {
+            mdns.set_hostname(&hostname)?;
+            mdns.set_instance_name(&hostname)?;
+            Ok(())
+        })?;
+        log::info!("mDNS hostname: {hostname}.local");
+        Ok(())
+    }
+
+    // Advertise service (eg. "_http", "_tcp")
+    pub fn add_service(
+        service_type: &str,
+        proto: &str,
+        port: u16,
+        txt: &[(&str, &str)],
+    ) -> anyhow::Result<()> {
+        Self::with_mdns(|mdns| Ok(mdns.add_service(None, service_type, proto, port, txt)?))
+    }
+
+    // Update service TXT record item
+    pub fn set_txt(service_type: &str, proto: &str, key: &str, value: &str) -> anyhow::Result<()> {
+        Self::with_mdns(|mdns| Ok(mdns.set_service_txt_item(service_type, proto, key, value)?))
+    }
+
+    fn with_mdns<F>(f: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut EspMdns) -> anyhow::Result<()>,
+    {
+        f(MDNS
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Mutex Error: {e}"))?
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("mDNS not initialised"))?)
+    }
+}
+
+// RFC 1123 label ([a-z0-9-], max 63 chars, no leading/trailing '-')
+pub fn validate_hostname(hostname: &str) -> anyhow::Result<()> {
+    if hostname.is_empty()
+        || hostname.len() > 63
+        || hostname.starts_with('-')
+        || hostname.ends_with('-')
+        || !hostname
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        anyhow::bail!("Invalid hostname: {hostname}");
+    }
+    Ok(())
+}
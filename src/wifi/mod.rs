@@ -1,24 +1,46 @@
 use esp_idf_svc::http::Method;
-use esp_idf_svc::ipv4::IpInfo;
+use esp_idf_svc::ipv4::{self, IpInfo};
+use esp_idf_svc::netif::{EspNetif, NetifConfiguration};
 use esp_idf_svc::wifi::{
-    AccessPointConfiguration, AccessPointInfo, AuthMethod, Configuration, EspWifi,
+    AccessPointConfiguration, AccessPointInfo, AuthMethod, ClientConfiguration, Configuration,
+    EspWifi,
 };
 
 use std::sync::Mutex;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 pub mod apstore;
+mod connection;
+pub mod dns;
+mod fallback;
+pub mod health;
+pub mod mdns;
+pub mod roam;
+mod security;
 pub mod web;
 
 // Exports
-pub use apstore::{APConfig, APStore};
+pub use apstore::{APConfig, APStats, APStore, Security};
+pub use connection::{request_scan, scan_pending, subscribe};
+pub use fallback::FallbackAP;
+pub use health::{HealthConfig, HealthStatus, NetworkHealth};
+pub use mdns::Mdns;
+pub use roam::{RoamConfig, RoamEvent};
 
 // Static scan results
 pub static WIFI_SCAN: Mutex<Vec<AccessPointInfo>> = Mutex::new(Vec::new());
+// Time of last scan
+pub static WIFI_SCAN_TIME: Mutex<Option<Instant>> = Mutex::new(None);
 
 pub struct WifiManager<'a> {
     wifi: EspWifi<'a>,
     visible: Option<Vec<AccessPointInfo>>,
+    // Current STA netif IP configuration (None if default)
+    sta_ip_configuration: Option<ipv4::ClientConfiguration>,
+    // Active AP configuration (station runs in AP+STA mode while set)
+    ap: Option<AccessPointConfiguration>,
+    // Enterprise CA certificate (NUL terminated PEM - referenced by supplicant)
+    eap_ca_cert: Option<Vec<u8>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -56,20 +78,78 @@ impl WifiState {
             }
             WifiState::AP(ap, ip) => ["Access Point", ap.ssid.as_ref(), &ip.ip.to_string()],
         };
-        key.into_iter()
+        let mut fields = key
+            .into_iter()
             .map(|s| s.to_string())
             .zip(value.into_iter().map(|s| s.to_string()))
-            .collect::<Vec<_>>()
+            .collect::<Vec<_>>();
+        if let WifiState::Station(_, _) = self {
+            if let (Some(bssid), Some(rssi)) = (sta_bssid(), sta_rssi()) {
+                fields.push(("BSSID".to_string(), roam::format_bssid(&bssid).to_string()));
+                fields.push(("RSSI".to_string(), format!("{rssi} dBm")));
+            }
+            fields.push(("Roams".to_string(), roam::roam_count().to_string()));
+            fields.push((
+                "Network Health".to_string(),
+                health::status().state.to_string(),
+            ));
+        }
+        fields
     }
 }
 
 const SLEEP_MS: u64 = 500;
 
+fn sta_ap_record() -> Option<esp_idf_sys::wifi_ap_record_t> {
+    let mut ap_info = esp_idf_sys::wifi_ap_record_t::default();
+    esp_idf_sys::esp!(unsafe { esp_idf_sys::esp_wifi_sta_get_ap_info(&mut ap_info) })
+        .ok()
+        .map(|_| ap_info)
+}
+
+// RSSI of currently connected AP (None if not connected)
+pub fn sta_rssi() -> Option<i8> {
+    sta_ap_record().map(|ap_info| ap_info.rssi)
+}
+
+// BSSID of currently connected AP (None if not connected)
+pub fn sta_bssid() -> Option<[u8; 6]> {
+    sta_ap_record().map(|ap_info| ap_info.bssid)
+}
+
+// Channel of currently connected AP (None if not connected)
+pub fn sta_channel() -> Option<u8> {
+    sta_ap_record().map(|ap_info| ap_info.primary)
+}
+
+// Number of stations connected to AP
+pub fn ap_station_count() -> usize {
+    let mut sta_list = esp_idf_sys::wifi_sta_list_t::default();
+    esp_idf_sys::esp!(unsafe { esp_idf_sys::esp_wifi_ap_get_sta_list(&mut sta_list) })
+        .map(|_| sta_list.num as usize)
+        .unwrap_or(0)
+}
+
+// Default device name using last 2 bytes of STA MAC (eg. Doorbell-A1B2)
+pub fn default_name(prefix: &str) -> String {
+    let mut mac = [0_u8; 6];
+    unsafe {
+        esp_idf_sys::esp_read_mac(
+            mac.as_mut_ptr(),
+            esp_idf_sys::esp_mac_type_t_ESP_MAC_WIFI_STA,
+        );
+    }
+    format!("{prefix}-{:02X}{:02X}", mac[4], mac[5])
+}
+
 impl<'a> WifiManager<'a> {
     pub fn new(wifi: EspWifi<'a>) -> anyhow::Result<Self> {
         Ok(Self {
             wifi,
             visible: None,
+            sta_ip_configuration: None,
+            ap: None,
+            eap_ca_cert: None,
         })
     }
 
@@ -81,6 +161,11 @@ impl<'a> WifiManager<'a> {
         server.add_handler("/wifi", Method::Get, web::wifi_handler(navbar))?;
         server.add_handler("/wifi/delete/*", Method::Get, web::ap_delete_handler())?;
         server.add_handler("/wifi/add", Method::Post, web::ap_add_handler())?;
+        server.add_handler("/wifi/hostname", Method::Post, web::hostname_handler())?;
+        server.add_handler("/wifi/fallback", Method::Post, web::fallback_handler())?;
+        server.add_handler("/wifi/roam", Method::Post, web::roam_handler())?;
+        server.add_handler("/wifi/health", Method::Post, web::health_handler())?;
+        server.add_handler("/api/wifi/scan", Method::Get, web::scan_api_handler())?;
         Ok(())
     }
 
@@ -91,11 +176,16 @@ impl<'a> WifiManager<'a> {
     }
 
     pub fn scan(&mut self) -> anyhow::Result<()> {
-        let config = Configuration::Client(esp_idf_svc::wifi::ClientConfiguration {
+        let config = self.sta_configuration(ClientConfiguration {
             ..Default::default()
         });
         self.wifi.set_configuration(&config)?;
         self.wifi.start()?;
+        self.scan_visible()
+    }
+
+    // Scan without changing configuration (background scan while connected)
+    pub fn scan_visible(&mut self) -> anyhow::Result<()> {
         self.visible = Some(
             self.wifi
                 .scan()?
@@ -118,6 +208,7 @@ impl<'a> WifiManager<'a> {
         // Save to WIFI_SCAN static (for web handler)
         let mut wifi_scan = WIFI_SCAN.lock().unwrap();
         *wifi_scan = self.visible.clone().unwrap(); // We know that visible is Some
+        WIFI_SCAN_TIME.replace(Some(Instant::now()))?;
         Ok(())
     }
 
@@ -127,19 +218,13 @@ impl<'a> WifiManager<'a> {
         local: Option<APConfig>,
         timeout_ms: u64,
     ) -> anyhow::Result<WifiState> {
-        // Check strongest signal first
-        let visible = self.visible.clone(); // Avoid borrow
-        if let Some(ref visible) = visible {
-            for ap in visible.iter().filter_map(|visible_ap| {
-                known
-                    .iter()
-                    .find(|&known_ap| visible_ap.ssid == known_ap.ssid)
-            }) {
-                if let Ok(WifiState::Station(ap, ip)) = self.connect_sta(ap, timeout_ms) {
-                    return Ok(WifiState::Station(ap, ip));
-                }
+        // Visible/hidden APs in priority order
+        let candidates = APStore::candidates(known, self.visible.as_deref().unwrap_or_default());
+        for ap in candidates.iter() {
+            if let Ok(WifiState::Station(ap, ip)) = self.connect_sta(ap, timeout_ms) {
+                return Ok(WifiState::Station(ap, ip));
             }
-        };
+        }
         // Unable to connect - if ap provided start in AP mode
         if let Some(local) = local {
             self.start_ap(&local)
@@ -149,13 +234,7 @@ impl<'a> WifiManager<'a> {
     }
 
     pub fn connect_sta(&mut self, config: &APConfig, timeout_ms: u64) -> anyhow::Result<WifiState> {
-        let sta_config = Configuration::Client(esp_idf_svc::wifi::ClientConfiguration {
-            ssid: config.ssid.clone(),
-            password: config.password.clone(),
-            ..Default::default()
-        });
-
-        self.wifi.set_configuration(&sta_config)?;
+        self.configure_sta(config)?;
         self.wifi.start()?;
         self.wifi.connect()?;
 
@@ -177,42 +256,187 @@ impl<'a> WifiManager<'a> {
                     timer += SLEEP_MS;
                     if timer >= timeout_ms {
                         self.wifi.stop()?;
+                        let _ = APStore::record_failure(&config.ssid);
                         return Ok(WifiState::NotConnected);
                     }
                 }
             }
         }
         let ip_info = self.wifi.sta_netif().get_ip_info()?;
+        if let Err(e) = APStore::record_success(&config.ssid) {
+            log::error!("Failed to update AP stats: {e}");
+        }
         Ok(WifiState::Station(config.clone(), ip_info))
     }
 
-    pub fn start_ap(&mut self, config: &APConfig) -> anyhow::Result<WifiState> {
-        let ap_config = if config.password.is_empty() {
-            AccessPointConfiguration {
-                ssid: config.ssid.clone(),
-                channel: 1,
-                auth_method: AuthMethod::None,
-                ..Default::default()
-            }
-        } else {
-            AccessPointConfiguration {
-                ssid: config.ssid.clone(),
-                password: config.password.clone(),
-                channel: 1,
-                auth_method: AuthMethod::WPA2Personal,
-                ..Default::default()
+    // Set station netif and configuration (including enterprise/WPA3 settings)
+    // for AP
+    pub fn configure_sta(&mut self, config: &APConfig) -> anyhow::Result<()> {
+        // AP only mode (start_ap) is replaced by station config
+        if self.ap.is_none() {
+            dns::stop();
+        }
+        self.set_sta_netif(config)?;
+        // Keeps AP up if active (AP+STA)
+        let sta_config = self.sta_configuration(config.client_configuration()?);
+        self.wifi.set_configuration(&sta_config)?;
+        // Clear previous credentials before releasing CA certificate
+        security::disable_enterprise()?;
+        self.eap_ca_cert = None;
+        match config.security {
+            Security::Personal => {}
+            Security::Wpa3 => security::require_sae()?,
+            Security::Enterprise => {
+                self.eap_ca_cert = APStore::get_ca_cert(&config.ssid)?.map(|pem| {
+                    let mut pem = pem.into_bytes();
+                    pem.push(0);
+                    pem
+                });
+                security::enable_enterprise(config, self.eap_ca_cert.as_deref())?;
             }
-        };
+        }
+        Ok(())
+    }
+
+    // Station configuration (AP+STA if AP is active)
+    fn sta_configuration(&self, client: ClientConfiguration) -> Configuration {
+        match &self.ap {
+            Some(ap) => Configuration::Mixed(client, ap.clone()),
+            None => Configuration::Client(client),
+        }
+    }
 
+    pub fn is_ap_active(&self) -> bool {
+        self.ap.is_some()
+    }
+
+    // Stop AP (leaving station connection up)
+    pub fn stop_ap(&mut self) -> anyhow::Result<()> {
+        if self.ap.take().is_some() {
+            // Set mode directly - set_configuration would reconfigure station
+            esp_idf_sys::esp!(unsafe {
+                esp_idf_sys::esp_wifi_set_mode(esp_idf_sys::wifi_mode_t_WIFI_MODE_STA)
+            })?;
+            dns::stop();
+            log::info!("Access Point stopped");
+        }
+        Ok(())
+    }
+
+    // Replace STA netif if AP IP configuration (static IP/hostname) has changed
+    fn set_sta_netif(&mut self, config: &APConfig) -> anyhow::Result<()> {
+        let ip_configuration = config.ip_configuration()?;
+        if self.sta_ip_configuration.as_ref() == Some(&ip_configuration) {
+            return Ok(());
+        }
+        log::info!("STA IP Configuration: {ip_configuration:?}");
+        let netif = EspNetif::new_with_conf(&NetifConfiguration {
+            ip_configuration: Some(ipv4::Configuration::Client(ip_configuration.clone())),
+            ..NetifConfiguration::wifi_default_client()
+        })?;
+        if self.wifi.is_started()? {
+            self.wifi.stop()?;
+        }
+        self.wifi.swap_netif_sta(netif)?;
+        self.sta_ip_configuration = Some(ip_configuration);
+        Ok(())
+    }
+
+    // Start AP only (WPA2 with captive portal - station is stopped)
+    pub fn start_ap(&mut self, config: &APConfig) -> anyhow::Result<WifiState> {
         self.wifi
-            .set_configuration(&Configuration::AccessPoint(ap_config))?;
+            .set_configuration(&Configuration::AccessPoint(ap_configuration(
+                config,
+                1,
+                AuthMethod::WPA2Personal,
+            )))?;
         self.wifi.start()?;
 
         let ip_info = self.wifi.ap_netif().get_ip_info()?;
 
-        log::info!("Access Point started: {config:?}");
+        log::info!("Access Point started: {}", config.ssid);
         log::info!("IpInfo: {ip_info:?}");
 
+        // Captive portal (stopped when station is configured)
+        if let Err(e) = dns::start(ip_info.ip) {
+            log::error!("Failed to start captive portal DNS: {e}");
+        }
+
         Ok(WifiState::AP(config.clone(), ip_info))
     }
+
+    // Start per-device fallback AP (channel/country/TX power from config). The
+    // station stays up (AP+STA) so known APs can be retried while AP is up
+    pub fn start_fallback_ap(&mut self, config: &FallbackAP) -> anyhow::Result<WifiState> {
+        config.set_country()?;
+        let ap_config = ap_configuration(
+            &config.ap_config(),
+            config.channel,
+            if config.wpa3 {
+                // WPA3-SAE with WPA2 fallback for older clients
+                AuthMethod::WPA2WPA3Personal
+            } else {
+                AuthMethod::WPA2Personal
+            },
+        );
+        self.wifi.set_configuration(&Configuration::Mixed(
+            ClientConfiguration::default(),
+            ap_config.clone(),
+        ))?;
+        self.wifi.start()?;
+        self.ap = Some(ap_config);
+        config.set_tx_power()?;
+
+        let ip_info = self.wifi.ap_netif().get_ip_info()?;
+
+        log::info!(
+            "Fallback Access Point started: {} [channel {}]",
+            config.ssid,
+            config.channel
+        );
+        log::info!("IpInfo: {ip_info:?}");
+        // Print (rather than log) so password is not forwarded by log backends
+        println!(
+            "Fallback AP: SSID: {} Password: {}",
+            config.ssid,
+            if config.password.is_empty() {
+                "<none>"
+            } else {
+                config.password.as_str()
+            }
+        );
+
+        // Captive portal (resolve all hostnames to AP)
+        if config.captive_portal {
+            if let Err(e) = dns::start(ip_info.ip) {
+                log::error!("Failed to start captive portal DNS: {e}");
+            }
+        }
+
+        Ok(WifiState::AP(config.ap_config(), ip_info))
+    }
+}
+
+// AP configuration (open if no password)
+fn ap_configuration(
+    config: &APConfig,
+    channel: u8,
+    auth_method: AuthMethod,
+) -> AccessPointConfiguration {
+    if config.password.is_empty() {
+        AccessPointConfiguration {
+            ssid: config.ssid.clone(),
+            channel,
+            auth_method: AuthMethod::None,
+            ..Default::default()
+        }
+    } else {
+        AccessPointConfiguration {
+            ssid: config.ssid.clone(),
+            password: config.password.clone(),
+            channel,
+            auth_method,
+            ..Default::default()
+        }
+    }
 }
@@ -0,0 +1,213 @@
+use esp_idf_svc::sys::{
+    esp, esp_wifi_get_config, esp_wifi_set_config, wifi_config_t, wifi_interface_t_WIFI_IF_STA,
+};
+use esp_idf_svc::wifi::AccessPointInfo;
+use serde::{Deserialize, Serialize};
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::APConfig;
+use crate::nvs::NVStore;
+
+// Roaming - when the signal from the current AP stays below the threshold a
+// background scan is made and the station reassociates to a known AP with
+// sufficiently better signal (AP initiated 802.11v transitions are handled by
+// the supplicant if enabled in sdkconfig)
+
+const ROAM_NVS_KEY: &str = "roam";
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct RoamConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // Roam if RSSI below threshold (dBm)
+    #[serde(default = "default_threshold")]
+    pub threshold: i8,
+    // Minimum RSSI improvement to roam (dB)
+    #[serde(default = "default_min_improvement")]
+    pub min_improvement: u8,
+    // Time RSSI must stay below threshold before scanning (secs)
+    #[serde(default = "default_low_duration")]
+    pub low_duration: u32,
+    // Minimum interval between roam scans (secs)
+    #[serde(default = "default_scan_interval")]
+    pub scan_interval: u32,
+}
+
+fn default_threshold() -> i8 {
+    -75
+}
+
+fn default_min_improvement() -> u8 {
+    8
+}
+
+fn default_low_duration() -> u32 {
+    30
+}
+
+fn default_scan_interval() -> u32 {
+    120
+}
+
+impl Default for RoamConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: default_threshold(),
+            min_improvement: default_min_improvement(),
+            low_duration: default_low_duration(),
+            scan_interval: default_scan_interval(),
+        }
+    }
+}
+
+impl RoamConfig {
+    pub fn get() -> anyhow::Result<Self> {
+        Ok(NVStore::get::<RoamConfig>(ROAM_NVS_KEY)?.unwrap_or_default())
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.validate()?;
+        NVStore::set::<RoamConfig>(ROAM_NVS_KEY, self)
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(-95..=-40).contains(&self.threshold) {
+            anyhow::bail!("Invalid threshold: {} (-95 to -40 dBm)", self.threshold);
+        }
+        if !(1..=40).contains(&self.min_improvement) {
+            anyhow::bail!(
+                "Invalid minimum improvement: {} (1-40 dB)",
+                self.min_improvement
+            );
+        }
+        if self.scan_interval < 30 {
+            anyhow::bail!("Invalid scan interval: {} (min 30s)", self.scan_interval);
+        }
+        Ok(())
+    }
+
+    // Strongest visible known AP at least min_improvement better than current
+    // (returns AP config pinned to BSSID/channel and scan result)
+    pub fn target<'a>(
+        &self,
+        known: &[APConfig],
+        visible: &'a [AccessPointInfo],
+        bssid: [u8; 6],
+        rssi: i8,
+    ) -> Option<(APConfig, &'a AccessPointInfo)> {
+        visible
+            .iter()
+            .filter(|v| v.bssid != bssid)
+            .filter(|v| v.signal_strength as i16 >= rssi as i16 + self.min_improvement as i16)
+            .filter_map(|v| known.iter().find(|ap| ap.matches(v)).map(|ap| (ap, v)))
+            .max_by_key(|(_, v)| v.signal_strength)
+            .map(|(ap, v)| {
+                let mut ap = ap.clone();
+                ap.bssid = format_bssid(&v.bssid);
+                ap.channel = v.channel;
+                (ap, v)
+            })
+    }
+}
+
+#[derive(Clone, Serialize, Debug, PartialEq, Eq)]
+pub struct RoamEvent {
+    // Unix time
+    pub time: u64,
+    pub ssid: String,
+    pub from_bssid: String,
+    pub from_rssi: i8,
+    pub to_bssid: String,
+    pub to_rssi: i8,
+    pub channel: u8,
+}
+
+impl RoamEvent {
+    pub fn new(ssid: &str, from: ([u8; 6], i8), to: &AccessPointInfo) -> Self {
+        Self {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            ssid: ssid.to_string(),
+            from_bssid: format_bssid(&from.0).to_string(),
+            from_rssi: from.1,
+            to_bssid: format_bssid(&to.bssid).to_string(),
+            to_rssi: to.signal_strength,
+            channel: to.channel,
+        }
+    }
+}
+
+impl std::fmt::Display for RoamEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} ({} dBm) -> {} ({} dBm)",
+            self.ssid, self.from_bssid, self.from_rssi, self.to_bssid, self.to_rssi
+        )
+    }
+}
+
+static ROAM_SUBSCRIBERS: Mutex<Vec<mpsc::Sender<RoamEvent>>> = Mutex::new(Vec::new());
+static ROAM_COUNT: AtomicU32 = AtomicU32::new(0);
+static LAST_ROAM: Mutex<Option<RoamEvent>> = Mutex::new(None);
+
+// Subscribe to completed roam events (published by connection thread)
+pub fn subscribe() -> mpsc::Receiver<RoamEvent> {
+    let (tx, rx) = mpsc::channel();
+    ROAM_SUBSCRIBERS.lock().unwrap().push(tx);
+    rx
+}
+
+pub(super) fn publish(event: RoamEvent) {
+    log::info!("WiFi Roamed: {event}");
+    ROAM_COUNT.fetch_add(1, Ordering::Relaxed);
+    let _ = LAST_ROAM.replace(Some(event.clone()));
+    ROAM_SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|tx| tx.send(event.clone()).is_ok());
+}
+
+// Roams since boot
+pub fn roam_count() -> u32 {
+    ROAM_COUNT.load(Ordering::Relaxed)
+}
+
+pub fn last_roam() -> Option<RoamEvent> {
+    LAST_ROAM.get_cloned().ok().flatten()
+}
+
+pub fn format_bssid(bssid: &[u8; 6]) -> heapless::String<17> {
+    let s = bssid
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(":");
+    heapless::String::try_from(s.as_str()).unwrap_or_default()
+}
+
+// Enable 802.11k (radio measurement) and 802.11v (BSS transition) in the
+// current station config so the AP can steer us (requires
+// CONFIG_WPA_11KV_SUPPORT - ignored by the supplicant otherwise)
+pub(super) fn enable_11kv() -> anyhow::Result<()> {
+    let mut config = wifi_config_t::default();
+    unsafe {
+        esp!(esp_wifi_get_config(
+            wifi_interface_t_WIFI_IF_STA,
+            &mut config
+        ))?;
+        config.sta.set_rm_enabled(1);
+        config.sta.set_btm_enabled(1);
+        esp!(esp_wifi_set_config(
+            wifi_interface_t_WIFI_IF_STA,
+            &mut config
+        ))?;
+    }
+    Ok(())
+}
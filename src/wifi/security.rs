@@ -0,0 +1,79 @@
+use esp_idf_svc::sys::{
+    esp, esp_eap_client_clear_ca_cert, esp_eap_client_clear_identity,
+    esp_eap_client_clear_password, esp_eap_client_clear_username, esp_eap_client_set_ca_cert,
+    esp_eap_client_set_identity, esp_eap_client_set_password, esp_eap_client_set_username,
+    esp_wifi_get_config, esp_wifi_set_config, esp_wifi_sta_enterprise_disable,
+    esp_wifi_sta_enterprise_enable, wifi_config_t, wifi_interface_t_WIFI_IF_STA,
+    wpa3_sae_pwe_method_t_WPA3_SAE_PWE_BOTH,
+};
+
+use super::APConfig;
+
+// Station security settings not covered by ClientConfiguration (call after
+// set_configuration)
+
+// Set EAP credentials and enable enterprise authentication. CA certificate
+// must be NUL terminated PEM and remain valid while enabled (the supplicant
+// keeps a pointer rather than a copy)
+pub(super) fn enable_enterprise(ap: &APConfig, ca_cert: Option<&[u8]>) -> anyhow::Result<()> {
+    let identity = if ap.identity.is_empty() {
+        ap.username.as_bytes()
+    } else {
+        ap.identity.as_bytes()
+    };
+    unsafe {
+        esp!(esp_eap_client_set_identity(
+            identity.as_ptr(),
+            identity.len() as i32
+        ))?;
+        esp!(esp_eap_client_set_username(
+            ap.username.as_ptr(),
+            ap.username.len() as i32
+        ))?;
+        esp!(esp_eap_client_set_password(
+            ap.password.as_ptr(),
+            ap.password.len() as i32
+        ))?;
+        match ca_cert {
+            Some(ca_cert) => esp!(esp_eap_client_set_ca_cert(
+                ca_cert.as_ptr(),
+                ca_cert.len() as i32
+            ))?,
+            None => esp_eap_client_clear_ca_cert(),
+        }
+        esp!(esp_wifi_sta_enterprise_enable())?;
+    }
+    Ok(())
+}
+
+// Disable enterprise authentication and clear credentials
+pub(super) fn disable_enterprise() -> anyhow::Result<()> {
+    unsafe {
+        esp!(esp_wifi_sta_enterprise_disable())?;
+        esp_eap_client_clear_identity();
+        esp_eap_client_clear_username();
+        esp_eap_client_clear_password();
+        esp_eap_client_clear_ca_cert();
+    }
+    Ok(())
+}
+
+// WPA3-SAE only - require PMF and allow both SAE PWE methods
+// (hunting-and-pecking/hash-to-element)
+pub(super) fn require_sae() -> anyhow::Result<()> {
+    let mut config = wifi_config_t::default();
+    unsafe {
+        esp!(esp_wifi_get_config(
+            wifi_interface_t_WIFI_IF_STA,
+            &mut config
+        ))?;
+        config.sta.pmf_cfg.capable = true;
+        config.sta.pmf_cfg.required = true;
+        config.sta.sae_pwe_h2e = wpa3_sae_pwe_method_t_WPA3_SAE_PWE_BOTH;
+        esp!(esp_wifi_set_config(
+            wifi_interface_t_WIFI_IF_STA,
+            &mut config
+        ))?;
+    }
+    Ok(())
+}
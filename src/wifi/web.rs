@@ -2,14 +2,68 @@ use esp_idf_svc::http::server::{EspHttpConnection, Request};
 
 use askama::Template;
 
-use crate::web::FlashMsg;
-use crate::wifi::{APStore, WIFI_SCAN};
+use crate::nvs::NV_STORE_MAX;
+use crate::template::format_utc;
+use crate::web::{read_body, FlashMsg};
+use crate::wifi::{
+    health, request_scan, roam, scan_pending, APConfig, APStore, FallbackAP, HealthConfig,
+    HealthStatus, Mdns, RoamConfig, WIFI_SCAN, WIFI_SCAN_TIME,
+};
+
+// Scan result (page and /api/wifi/scan)
+#[derive(serde::Serialize)]
+struct ScanResult {
+    ssid: String,
+    rssi: i8,
+    channel: u8,
+    auth: String,
+    known: bool,
+}
+
+fn scan_results(known: &[APConfig]) -> Vec<ScanResult> {
+    WIFI_SCAN
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|ap| ScanResult {
+            ssid: ap.ssid.to_string(),
+            rssi: ap.signal_strength,
+            channel: ap.channel,
+            auth: match ap.auth_method {
+                Some(m) => format!("{m:?}"),
+                None => "None".to_string(),
+            },
+            known: known.iter().any(|k| k.matches(ap)),
+        })
+        .collect()
+}
+
+#[derive(serde::Serialize)]
+struct ScanResponse {
+    // False while requested scan is pending (results may be stale)
+    scanned: bool,
+    // Age of results (secs)
+    age: Option<u64>,
+    aps: Vec<ScanResult>,
+}
 
 #[derive(askama::Template)]
 #[template(path = "wifi.html")]
 struct WiFiConfig<'a> {
-    visible: Vec<(&'a str, u8, i8, String)>,
-    aps: Vec<&'a str>,
+    visible: Vec<ScanResult>,
+    // (ssid, ip, priority, hidden, last connected, failures)
+    aps: Vec<(&'a str, &'a str, u8, bool, String, u32)>,
+    // AP being edited (/wifi?edit=<ssid>)
+    edit: APConfig,
+    editing: bool,
+    // Edited AP has enterprise CA certificate
+    edit_ca_cert: bool,
+    hostname: String,
+    fallback: Option<FallbackAP>,
+    roam: RoamConfig,
+    last_roam: String,
+    health_config: HealthConfig,
+    health: HealthStatus,
     navbar: crate::web::NavBar<'static>,
 }
 
@@ -17,25 +71,58 @@ pub fn wifi_handler(
     navbar: crate::web::NavBar<'static>,
 ) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static {
     move |request| {
-        let aps = APStore::get_aps()?;
-        let visible = WIFI_SCAN.lock().unwrap();
-        let visible = visible
-            .iter()
-            .map(|ap| {
-                (
-                    ap.ssid.as_str(),
-                    ap.channel,
-                    ap.signal_strength,
-                    match ap.auth_method {
-                        Some(m) => format!("{m:?}"),
-                        None => "None".to_string(),
-                    },
-                )
-            })
-            .collect::<Vec<_>>();
+        let mut aps = APStore::get_aps()?;
+        aps.sort_by(|a, b| b.priority.cmp(&a.priority).then(a.ssid.cmp(&b.ssid)));
+        let stats = APStore::get_stats()?;
+        let edit = match request.uri().split_once("?edit=") {
+            Some((_, ssid)) => APStore::get_ap_str(&urlencoding::decode(ssid)?)?,
+            None => None,
+        };
         let config_page = WiFiConfig {
-            visible,
-            aps: aps.iter().map(|s| s.ssid.as_str()).collect::<Vec<_>>(),
+            visible: scan_results(&aps),
+            aps: aps
+                .iter()
+                .map(|s| {
+                    let stats = stats.get(&s.ssid).cloned().unwrap_or_default();
+                    (
+                        s.ssid.as_str(),
+                        if s.is_static() { s.ip.as_str() } else { "DHCP" },
+                        s.priority,
+                        s.hidden,
+                        if stats.last_connected > 0 {
+                            format_utc(stats.last_connected)
+                        } else {
+                            "Never".to_string()
+                        },
+                        stats.failures,
+                    )
+                })
+                .collect::<Vec<_>>(),
+            editing: edit.is_some(),
+            edit_ca_cert: match &edit {
+                Some(ap) => APStore::get_ca_cert(&ap.ssid)?.is_some(),
+                None => false,
+            },
+            edit: edit
+                .map(|ap| APConfig {
+                    // Password is not displayed (empty keeps current password)
+                    password: Default::default(),
+                    ..ap
+                })
+                .unwrap_or_default(),
+            hostname: Mdns::hostname()?,
+            // Password is not displayed (empty keeps current password)
+            fallback: FallbackAP::get()?.map(|fallback| FallbackAP {
+                password: Default::default(),
+                ..fallback
+            }),
+            roam: RoamConfig::get()?,
+            last_roam: match roam::last_roam() {
+                Some(event) => format!("{} [{event}]", format_utc(event.time)),
+                None => "Never".to_string(),
+            },
+            health_config: HealthConfig::get()?,
+            health: health::status(),
             navbar: navbar.clone(),
         };
         let mut response = request.into_ok_response()?;
@@ -77,18 +164,61 @@ pub fn ap_delete_handler(
     }
 }
 
+// Enterprise CA certificate fields (stored separately from APConfig)
+#[derive(serde::Deserialize)]
+struct CaCertForm {
+    #[serde(default)]
+    ca_cert: String,
+    #[serde(default)]
+    clear_ca_cert: bool,
+}
+
+impl CaCertForm {
+    // Check PEM before AP is saved (so the certificate save is unlikely to fail)
+    fn validate(&self) -> anyhow::Result<()> {
+        let pem = self.ca_cert.trim();
+        if !self.clear_ca_cert && !pem.is_empty() && !pem.starts_with("-----BEGIN CERTIFICATE-----")
+        {
+            anyhow::bail!("Invalid CA certificate (PEM required)");
+        }
+        Ok(())
+    }
+
+    fn save(&self, ssid: &str) -> anyhow::Result<()> {
+        if self.clear_ca_cert {
+            APStore::delete_ca_cert(ssid)
+        } else if !self.ca_cert.trim().is_empty() {
+            APStore::set_ca_cert(ssid, &self.ca_cert)
+        } else {
+            Ok(())
+        }
+    }
+}
+
 pub fn ap_add_handler(
 ) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static {
     move |mut request| {
-        // Read the body of the request
-        let mut buf = [0_u8; 256];
-        let len = request.read(&mut buf)?;
+        // Allow for CA certificate (urlencoded)
+        let body = read_body(&mut request, 2 * NV_STORE_MAX)?;
 
-        match serde_urlencoded::from_bytes(&buf[0..len]) {
-            Ok(config) => {
+        match serde_urlencoded::from_bytes::<APConfig>(&body)
+            .and_then(|config| Ok((config, serde_urlencoded::from_bytes::<CaCertForm>(&body)?)))
+        {
+            Ok((mut config, ca_cert)) => {
+                // Keep current password if not provided (edit)
+                if config.password.is_empty() {
+                    if let Some(current) = APStore::get_ap(&config.ssid)? {
+                        config.password = current.password;
+                    }
+                }
                 // Save the WiFi configuration
-                log::info!("Wifi Config: {config:?}");
-                let (level, message) = match APStore::add_ap(&config) {
+                log::info!("Wifi Config: {}", config.ssid);
+                let (level, message) = match config
+                    .validate()
+                    .and_then(|_| ca_cert.validate())
+                    .and_then(|_| APStore::add_ap(&config))
+                    .and_then(|_| ca_cert.save(&config.ssid))
+                {
                     Ok(_) => (
                         "success",
                         &format!("Successfully saved SSID: {}", config.ssid),
@@ -116,3 +246,151 @@ pub fn ap_add_handler(
         Ok::<(), anyhow::Error>(())
     }
 }
+
+#[derive(serde::Deserialize)]
+struct HostnameForm {
+    hostname: String,
+}
+
+pub fn hostname_handler(
+) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static {
+    move |mut request| {
+        let body = read_body(&mut request, 256)?;
+        let (level, message) = match serde_urlencoded::from_bytes::<HostnameForm>(&body)
+            .map_err(anyhow::Error::from)
+            .and_then(|form| Mdns::set_hostname(&form.hostname))
+        {
+            Ok(_) => ("success", &format!("Hostname: {}.local", Mdns::hostname()?)),
+            Err(e) => ("error", &format!("Failed to set hostname [{e}]")),
+        };
+        log::info!("{level}: {message}");
+        request.into_response(
+            302,
+            Some(message),
+            &[
+                ("Location", "/wifi"),
+                ("Set-Cookie", &FlashMsg::cookie(level, message)?),
+            ],
+        )?;
+        Ok::<(), anyhow::Error>(())
+    }
+}
+
+pub fn fallback_handler(
+) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static {
+    move |mut request| {
+        let body = read_body(&mut request, 512)?;
+        let result = serde_urlencoded::from_bytes::<FallbackAP>(&body)
+            .map_err(anyhow::Error::from)
+            .and_then(|mut config| {
+                // Keep current password if not provided
+                if config.password.is_empty() {
+                    if let Some(current) = FallbackAP::get()? {
+                        config.password = current.password;
+                    }
+                }
+                // Unchecked checkbox is not submitted (serde default is enabled)
+                config.captive_portal =
+                    serde_urlencoded::from_bytes::<Vec<(String, String)>>(&body)?
+                        .iter()
+                        .any(|(k, v)| k == "captive_portal" && v == "true");
+                config.save()
+            });
+        let (level, message) = match result {
+            Ok(_) => (
+                "success",
+                "Fallback AP updated (applied on restart)".to_string(),
+            ),
+            Err(e) => ("error", format!("Failed to update fallback AP [{e}]")),
+        };
+        log::info!("{level}: {message}");
+        request.into_response(
+            302,
+            Some(&message),
+            &[
+                ("Location", "/wifi"),
+                ("Set-Cookie", &FlashMsg::cookie(level, &message)?),
+            ],
+        )?;
+        Ok::<(), anyhow::Error>(())
+    }
+}
+
+pub fn roam_handler(
+) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static {
+    move |mut request| {
+        let body = read_body(&mut request, 256)?;
+        let (level, message) = match serde_urlencoded::from_bytes::<RoamConfig>(&body)
+            .map_err(anyhow::Error::from)
+            .and_then(|config| config.save())
+        {
+            Ok(_) => ("success", "Roaming settings updated".to_string()),
+            Err(e) => ("error", format!("Failed to update roaming settings [{e}]")),
+        };
+        log::info!("{level}: {message}");
+        request.into_response(
+            302,
+            Some(&message),
+            &[
+                ("Location", "/wifi"),
+                ("Set-Cookie", &FlashMsg::cookie(level, &message)?),
+            ],
+        )?;
+        Ok::<(), anyhow::Error>(())
+    }
+}
+
+pub fn health_handler(
+) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static {
+    move |mut request| {
+        let body = read_body(&mut request, 512)?;
+        let (level, message) = match serde_urlencoded::from_bytes::<HealthConfig>(&body)
+            .map_err(anyhow::Error::from)
+            .and_then(|config| config.save())
+        {
+            Ok(_) => ("success", "Health check settings updated".to_string()),
+            Err(e) => (
+                "error",
+                format!("Failed to update health check settings [{e}]"),
+            ),
+        };
+        log::info!("{level}: {message}");
+        request.into_response(
+            302,
+            Some(&message),
+            &[
+                ("Location", "/wifi"),
+                ("Set-Cookie", &FlashMsg::cookie(level, &message)?),
+            ],
+        )?;
+        Ok::<(), anyhow::Error>(())
+    }
+}
+
+// Return current scan results as JSON (?refresh=true requests background
+// scan - results are returned immediately so poll until scanned is true)
+pub fn scan_api_handler(
+) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static {
+    move |request| {
+        let refresh = request
+            .uri()
+            .split_once('?')
+            .is_some_and(|(_, query)| query.split('&').any(|p| p == "refresh=true"));
+        if refresh {
+            if let Err(e) = request_scan() {
+                log::error!("WiFi Scan Error: {e}");
+            }
+        }
+        let scan = ScanResponse {
+            scanned: !scan_pending(),
+            age: WIFI_SCAN_TIME
+                .get_cloned()?
+                .map(|time| time.elapsed().as_secs()),
+            aps: scan_results(&APStore::get_aps()?),
+        };
+        let mut response =
+            request.into_response(200, Some("OK"), &[("Content-Type", "application/json")])?;
+        response.write(&serde_json::to_vec(&scan)?)?;
+        Ok::<(), anyhow::Error>(())
+    }
+}